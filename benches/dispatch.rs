@@ -0,0 +1,63 @@
+// Code size and running time of some benchmark programs. Run with
+// `cargo bench`.
+extern crate cam;
+
+use std::mem;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::time::{Duration,Instant};
+
+use cam::cam::*;
+use cam::compile::compile;
+use cam::lang::*;
+use cam::parse::ParseFrom;
+use cam::sexp::Sexp;
+
+const BENCHES: &'static [(&'static str, &'static str)] = &[
+    ("fib",
+     "((fn (fib) (fib fib 24))
+       (fn (self n) (if (le n 1) n
+                        (add (self self (sub n 1)) (self self (sub n 2))))))"),
+    ("count",
+     "((fn (loop) (loop loop 0 300000))
+       (fn (self acc n)
+         (if (eq n 0) acc (self self (add acc 1) (sub n 1)))))"),
+    ("closures",
+     "((fn (loop) (loop loop 0 100000))
+       (fn (self acc n)
+         (if (eq n 0) acc
+             (let ((f (fn (x) (add x n))))
+               (self self (f acc) (sub n 1))))))"),
+    ("strings",
+     "((fn (loop) (loop loop \"\" 2000))
+       (fn (self s n)
+         (if (eq n 0) (string-length s)
+             (self self (string-append s (if (eq (sub n (mul 2 (truncate (div n 2)))) 0)
+                                             \"ab\" \"c\"))
+                   (sub n 1)))))"),
+];
+
+// Bytes taken by the code itself, not counting what literals point to.
+fn code_size(proto: &Proto) -> usize {
+    mem::size_of::<Proto>() + proto.code.len()
+        + proto.consts.len() * mem::size_of::<Lit>()
+        + proto.protos.len() * mem::size_of::<Rc<Proto>>()
+        + proto.protos.iter().map(|p| code_size(p)).sum::<usize>()
+}
+
+fn time(e: &Exp) -> Duration {
+    (0..5).map(|_| {
+        let code = compile(e);
+        let start = Instant::now();
+        VM::run(code).unwrap();
+        start.elapsed()
+    }).min().unwrap()
+}
+
+fn main() {
+    for &(name, src) in BENCHES {
+        let e = Exp::parse_from(&Sexp::from_str(src).unwrap()).unwrap();
+        println!("{:10} {:6} bytes {:>9.2} ms", name, code_size(&compile(&e)),
+                 time(&e).as_secs_f64() * 1000.0);
+    }
+}
@@ -0,0 +1,57 @@
+// Timings for let-heavy programs. Run with `cargo bench`.
+extern crate cam;
+
+use std::str::FromStr;
+use std::time::{Duration,Instant};
+
+use cam::cam::VM;
+use cam::compile::compile;
+use cam::lang::*;
+use cam::parse::ParseFrom;
+use cam::sexp::Sexp;
+
+const BENCHES: &'static [(&'static str, &'static str)] = &[
+    // lets in tail position, around a tail call.
+    ("tail-lets",
+     "((fn (loop) (loop loop 0 200000))
+       (fn (self acc n)
+         (if (eq n 0) acc
+             (let ((a (add acc 1)) (b n))
+               (let ((c (sub b 1)))
+                 (self self a c))))))"),
+    // lets whose values are used by the surrounding expression.
+    ("nested-lets",
+     "((fn (loop) (loop loop 0 200000))
+       (fn (self acc n)
+         (if (eq n 0) acc
+             (self self
+                   (add acc (let ((x n)) (let ((y (add x 1))) (sub y x))))
+                   (sub n 1)))))"),
+    // lets whose variables are captured and mutated.
+    ("boxed-lets",
+     "((fn (loop) (loop loop 0 100000))
+       (fn (self acc n)
+         (if (eq n 0) acc
+             (let ((x acc))
+               (let ((bump (fn () (set! x (add x 1)))))
+                 (let ((u (bump)))
+                   (self self x (sub n 1))))))))"),
+];
+
+fn time(src: &str) -> Duration {
+    let s = Sexp::from_str(src).unwrap();
+    let e = Exp::parse_from(&s).unwrap();
+    (0..5).map(|_| {
+        let code = compile(&e);
+        let start = Instant::now();
+        VM::run(code).unwrap();
+        start.elapsed()
+    }).min().unwrap()
+}
+
+fn main() {
+    for &(name, src) in BENCHES {
+        let t = time(src);
+        println!("{:12} {:>8.2} ms", name, t.as_secs_f64() * 1000.0);
+    }
+}
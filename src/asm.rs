@@ -0,0 +1,251 @@
+// Reads code written by hand, in the format the disassembler prints, so the
+// VM can be tested without going through lang and compile:
+//
+//   (proto p0 (arity 0)
+//       (push true)
+//       (if L2 L4)
+//   L2: (closure p1)
+//       (return)
+//   L4: (closure (proto (arity 1) (get 0) (return)))
+//       (return))
+//   (proto p1 (arity 0) (push 1) (return))
+//
+// A symbol ending in a colon labels the next instruction, and jumps name
+// their targets by label (or by number). Numbers between instructions are
+// ignored, as are ; comments. A closure's proto is either written in place,
+// or named by the id of another proto in the same listing.
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use cam::*;
+use lang::*;
+use parse::ParseFrom;
+use sexp::{Sexp,SexpKind,Span};
+use string::Symbol;
+
+type ParseResult<A> = Result<A, SyntaxError>;
+
+// A single instruction. Its targets must be numbers, and its closure's proto
+// written in place. With no proto around it, any target will do.
+impl<'a> ParseFrom<&'a Sexp> for Instr {
+    type Error = SyntaxError;
+    fn parse_from(s: &Sexp) -> ParseResult<Instr> {
+        Asm { forms: HashMap::new(), done: HashMap::new(), active: vec![] }
+            .instr(s, &HashMap::new(), InstrIndex::max_value() as usize)
+    }
+}
+
+// A single proto, whose closures' protos are written in place.
+impl<'a> ParseFrom<&'a Sexp> for Proto {
+    type Error = SyntaxError;
+    fn parse_from(s: &Sexp) -> ParseResult<Proto> {
+        Asm { forms: HashMap::new(), done: HashMap::new(), active: vec![] }
+            .proto(s)
+    }
+}
+
+// A listing of protos, as printed by the disassembler. The first is the one
+// returned.
+impl<'a> ParseFrom<&'a [Sexp]> for Proto {
+    type Error = SyntaxError;
+    fn parse_from(forms: &[Sexp]) -> ParseResult<Proto> {
+        let mut asm = Asm { forms: HashMap::new(), done: HashMap::new(),
+                            active: vec![] };
+        for form in forms {
+            if let Some(id) = try!(header(form)).0 {
+                if asm.forms.insert(id.clone(), form).is_some() {
+                    return Err(SyntaxError::DuplicateProto(id, form.span))
+                }
+            }
+        }
+        let form = match forms.first() {
+            Some(form) => form,
+            None => return Err(SyntaxError::MalformedProto(
+                Span { start: 0, end: 0 })),
+        };
+        if let Some(id) = try!(header(form)).0 { asm.active.push(id) }
+        asm.proto(form)
+    }
+}
+
+struct Asm<'a> {
+    // The protos in the listing, by id.
+    forms: HashMap<Ident, &'a Sexp>,
+    // Those assembled so far. Several closures may share one.
+    done: HashMap<Ident, Rc<Proto>>,
+    // Those being assembled, to catch a proto that contains itself.
+    active: Vec<Ident>,
+}
+
+// Splits (proto [id] (arity n) item...) into its id, arity and items.
+fn header(s: &Sexp) -> ParseResult<(Option<Ident>, Arity, &[Sexp])> {
+    let malformed = SyntaxError::MalformedProto(s.span);
+    let items = match s.kind {
+        SexpKind::List(ref items) if is_symbol(items.first(), "proto") =>
+            &items[1..],
+        _ => return Err(malformed),
+    };
+    let (id, items) = match items.first().map(|i| &i.kind) {
+        Some(&SexpKind::Symbol(ref id)) => (Some(id.clone()), &items[1..]),
+        _ => (None, items),
+    };
+    let arity = match items.first().map(|i| &i.kind) {
+        Some(&SexpKind::List(ref a)) if a.len() == 2
+            && is_symbol(a.first(), "arity") => try!(number(&a[1])),
+        _ => return Err(malformed),
+    };
+    Ok((id, arity, &items[1..]))
+}
+
+fn is_symbol(s: Option<&Sexp>, name: &str) -> bool {
+    match s.map(|s| &s.kind) {
+        Some(&SexpKind::Symbol(ref s)) => &**s == name,
+        _ => false,
+    }
+}
+
+fn number(s: &Sexp) -> ParseResult<u32> {
+    match s.kind {
+        SexpKind::Int(n) if 0 <= n && n <= u32::max_value() as i64 =>
+            Ok(n as u32),
+        _ => Err(SyntaxError::BadOperand(s.span)),
+    }
+}
+
+// The name of the label `s' defines, if it's a label.
+fn label(s: &Sexp) -> Option<Ident> {
+    match s.kind {
+        SexpKind::Symbol(ref name) if name.len() > 1 && name.ends_with(':') =>
+            Some(Symbol::intern(&name[..name.len() - 1])),
+        _ => None,
+    }
+}
+
+fn operands<'s>(form: &'static str, expected: usize, s: &Sexp, args: &'s [Sexp])
+                -> ParseResult<&'s [Sexp]>
+{
+    if args.len() == expected { return Ok(args) }
+    Err(SyntaxError::BadArity { form: form, expected: expected,
+                                got: args.len(), span: s.span })
+}
+
+impl<'a> Asm<'a> {
+    fn proto(&mut self, s: &Sexp) -> ParseResult<Proto> {
+        let (_, arity, items) = try!(header(s));
+
+        // Labels can be used before they're defined, so find them first.
+        let mut labels = HashMap::new();
+        let mut count = 0;
+        for item in items {
+            match item.kind {
+                SexpKind::List(_) => count += 1,
+                SexpKind::Int(_) => {}
+                _ => match label(item) {
+                    Some(name) => if labels.insert(name.clone(), count)
+                                                .is_some() {
+                        return Err(SyntaxError::DuplicateLabel(name, item.span))
+                    },
+                    None => return Err(SyntaxError::MalformedProto(item.span)),
+                },
+            }
+        }
+
+        let mut code = vec![];
+        for item in items {
+            if let SexpKind::List(_) = item.kind {
+                code.push(try!(self.instr(item, &labels, count)))
+            }
+        }
+        Ok(Proto::assemble(arity, &code))
+    }
+
+    // `len' is the number of instructions in the proto; a label can mark
+    // its end, but nothing beyond.
+    fn instr(&mut self, s: &Sexp, labels: &HashMap<Ident, usize>, len: usize)
+             -> ParseResult<Instr>
+    {
+        use cam::Instr::*;
+        let (name, args) = match s.kind {
+            SexpKind::List(ref items) => match items.first().map(|i| &i.kind) {
+                Some(&SexpKind::Symbol(ref name)) => (name.clone(), &items[1..]),
+                _ => return Err(SyntaxError::ExpectedSymbol(s.span)),
+            },
+            _ => return Err(SyntaxError::ExpectedSymbol(s.span)),
+        };
+        let target = |s: &Sexp| -> ParseResult<InstrIndex> {
+            let index = match s.kind {
+                SexpKind::Symbol(ref name) => match labels.get(name) {
+                    Some(&index) => index,
+                    None => return Err(SyntaxError::UnknownLabel(name.clone(),
+                                                                 s.span)),
+                },
+                _ => try!(number(s)) as usize,
+            };
+            if index > len || index > InstrIndex::max_value() as usize {
+                return Err(SyntaxError::BadOperand(s.span))
+            }
+            Ok(index as InstrIndex)
+        };
+        let one = |form| operands(form, 1, s, args).and_then(|a| number(&a[0]));
+        Ok(match &*name {
+            "get" => Get(try!(one("get"))),
+            "set" => Set(try!(one("set"))),
+            "box" => BoxVar(try!(one("box"))),
+            "get-box" => GetBox(try!(one("get-box"))),
+            "set-box" => SetBox(try!(one("set-box"))),
+            "push" => Push(try!(operands("push", 1, s, args)
+                               .and_then(|a| lit(&a[0])))),
+            "pop" => { try!(operands("pop", 0, s, args)); Pop }
+            "bind" => Bind(try!(one("bind"))),
+            "unbind" => Unbind(try!(one("unbind"))),
+            "apply" => Apply(try!(one("apply"))),
+            "tail-apply" => TailApply(try!(one("tail-apply"))),
+            "prim" => Prim(try!(operands("prim", 1, s, args)
+                               .and_then(|a| ::lang::Prim::parse_from(&a[0])))),
+            "closure" => {
+                let args = try!(operands("closure", 1, s, args));
+                Closure(try!(self.closure(&args[0])))
+            }
+            "if" => {
+                let args = try!(operands("if", 2, s, args));
+                If(try!(target(&args[0])), try!(target(&args[1])))
+            }
+            "jump" => Jump(try!(operands("jump", 1, s, args)
+                               .and_then(|a| target(&a[0])))),
+            "return" => { try!(operands("return", 0, s, args)); Return }
+            _ => return Err(SyntaxError::UnknownInstr(name, s.span)),
+        })
+    }
+
+    fn closure(&mut self, s: &Sexp) -> ParseResult<Rc<Proto>> {
+        let id = match s.kind {
+            SexpKind::Symbol(ref id) => id.clone(),
+            _ => return self.proto(s).map(Rc::new),
+        };
+        if let Some(proto) = self.done.get(&id) { return Ok(proto.clone()) }
+        let form = match self.forms.get(&id) {
+            Some(&form) => form,
+            None => return Err(SyntaxError::UnknownProto(id, s.span)),
+        };
+        if self.active.contains(&id) {
+            return Err(SyntaxError::RecursiveProto(id, s.span))
+        }
+        self.active.push(id.clone());
+        let proto = Rc::new(try!(self.proto(form)));
+        self.active.pop();
+        self.done.insert(id, proto.clone());
+        Ok(proto)
+    }
+}
+
+// Symbols are quoted, as in lang.
+fn lit(s: &Sexp) -> ParseResult<Lit> {
+    if let SexpKind::List(ref items) = s.kind {
+        if items.len() == 2 && is_symbol(items.first(), "quote") {
+            if let SexpKind::Symbol(ref sym) = items[1].kind {
+                return Ok(Lit::Symbol(sym.clone()))
+            }
+        }
+    }
+    Lit::parse_from(s)
+}
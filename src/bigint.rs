@@ -0,0 +1,342 @@
+// Arbitrary-precision integers.
+//
+// These only exist to catch fixnum overflow, so they favour simplicity over
+// speed: schoolbook multiplication and Knuth's algorithm D for division.
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+// Magnitudes are little-endian base-2^32 digits, with no trailing zeros. Zero
+// is the empty magnitude, and is never negative.
+#[derive(Clone,PartialEq,Eq,Hash,Debug)]
+pub struct BigInt { neg: bool, mag: Vec<u32> }
+
+impl BigInt {
+    fn new(neg: bool, mut mag: Vec<u32>) -> BigInt {
+        while mag.last() == Some(&0) { mag.pop(); }
+        BigInt { neg: neg && !mag.is_empty(), mag: mag }
+    }
+
+    pub fn zero() -> BigInt { BigInt { neg: false, mag: vec![] } }
+    pub fn one() -> BigInt { BigInt { neg: false, mag: vec![1] } }
+
+    pub fn from_i64(x: i64) -> BigInt {
+        let m = x.unsigned_abs();
+        BigInt::new(x < 0, vec![m as u32, (m >> 32) as u32])
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.mag.len() > 2 { return None }
+        let m = self.mag.iter().rev().fold(0u64, |acc, &d| acc << 32 | d as u64);
+        if self.neg {
+            if m <= 1 << 63 { Some((m as i64).wrapping_neg()) } else { None }
+        } else {
+            if m < 1 << 63 { Some(m as i64) } else { None }
+        }
+    }
+
+    // Rounds to nearest, ties to even.
+    pub fn to_f64(&self) -> f64 {
+        // Converting digit by digit would round more than once. Instead take
+        // the top 64 bits, setting the lowest if any bits below them are set;
+        // that's below where a float rounds, so the conversion rounds as if
+        // it had the whole number.
+        let shift = self.bits().saturating_sub(64);
+        let mut top = 0u64;
+        for i in (shift..self.bits()).rev() { top = top << 1 | self.bit(i) }
+        if (0..shift).any(|i| self.bit(i) == 1) { top |= 1 }
+        let m = top as f64 * 2f64.powi(shift as i32);
+        if self.neg { -m } else { m }
+    }
+
+    // Exact conversion from an integral float.
+    pub fn from_f64(x: f64) -> Option<BigInt> {
+        if !x.is_finite() || x.fract() != 0.0 { return None }
+        if x == 0.0 { return Some(BigInt::zero()) }
+        let bits = x.abs().to_bits();
+        let exp = (bits >> 52) as i32 - 1075;
+        let mantissa = bits & ((1 << 52) - 1) | 1 << 52;
+        let m = BigInt::from_i64(mantissa as i64);
+        let m = if exp >= 0 { m.shl(exp as usize) }
+                else { BigInt::from_i64((mantissa >> -exp) as i64) };
+        Some(if x < 0.0 { m.neg() } else { m })
+    }
+
+    pub fn is_zero(&self) -> bool { self.mag.is_empty() }
+    pub fn is_one(&self) -> bool { !self.neg && self.mag == [1] }
+    pub fn is_negative(&self) -> bool { self.neg }
+
+    pub fn neg(&self) -> BigInt { BigInt::new(!self.neg, self.mag.clone()) }
+    pub fn abs(&self) -> BigInt { BigInt::new(false, self.mag.clone()) }
+
+    pub fn add(&self, other: &BigInt) -> BigInt {
+        if self.neg == other.neg {
+            return BigInt::new(self.neg, add_mag(&self.mag, &other.mag))
+        }
+        match cmp_mag(&self.mag, &other.mag) {
+            Ordering::Less =>
+                BigInt::new(other.neg, sub_mag(&other.mag, &self.mag)),
+            _ => BigInt::new(self.neg, sub_mag(&self.mag, &other.mag)),
+        }
+    }
+
+    pub fn sub(&self, other: &BigInt) -> BigInt { self.add(&other.neg()) }
+
+    pub fn mul(&self, other: &BigInt) -> BigInt {
+        BigInt::new(self.neg != other.neg, mul_mag(&self.mag, &other.mag))
+    }
+
+    // Truncating division; the remainder has the sign of the dividend.
+    // Panics on division by zero.
+    pub fn div_rem(&self, other: &BigInt) -> (BigInt, BigInt) {
+        assert!(!other.is_zero(), "division by zero");
+        let (q, r) = div_rem_mag(&self.mag, &other.mag);
+        (BigInt::new(self.neg != other.neg, q), BigInt::new(self.neg, r))
+    }
+
+    // Always non-negative.
+    pub fn gcd(&self, other: &BigInt) -> BigInt {
+        let (mut a, mut b) = (self.abs(), other.abs());
+        while !b.is_zero() {
+            let r = a.div_rem(&b).1;
+            a = b;
+            b = r;
+        }
+        a
+    }
+
+    // The square root of a non-negative number, rounded down. Panics on
+    // negative numbers.
+    pub fn isqrt(&self) -> BigInt {
+        assert!(!self.neg, "square root of a negative number");
+        if self.is_zero() { return BigInt::zero() }
+        // Newton's method, from a power of two above the root, converges
+        // from above.
+        let two = BigInt::from_i64(2);
+        let mut x = BigInt::one().shl((self.bits() + 1) / 2);
+        loop {
+            let y = x.add(&self.div_rem(&x).0).div_rem(&two).0;
+            if y >= x { return x }
+            x = y;
+        }
+    }
+
+    // The number of bits in the magnitude.
+    pub fn bits(&self) -> usize {
+        match self.mag.last() {
+            None => 0,
+            Some(&top) => 32 * self.mag.len() - top.leading_zeros() as usize,
+        }
+    }
+
+    // Bit `i' of the magnitude.
+    fn bit(&self, i: usize) -> u64 { (self.mag[i / 32] >> (i % 32)) as u64 & 1 }
+
+    pub fn shl(&self, bits: usize) -> BigInt {
+        let mut mag = vec![0; bits / 32];
+        mag.extend(shl_mag(&self.mag, (bits % 32) as u32));
+        BigInt::new(self.neg, mag)
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &BigInt) -> Ordering {
+        match (self.neg, other.neg) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_mag(&self.mag, &other.mag),
+            (true, true) => cmp_mag(&other.mag, &self.mag),
+        }
+    }
+}
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &BigInt) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        if self.is_zero() { return f.write_str("0") }
+        // Peel off base-10^9 chunks, least significant first.
+        let mut chunks = vec![];
+        let mut mag = self.mag.clone();
+        while !mag.is_empty() {
+            let (q, r) = div_rem_small(&mag, 1000000000);
+            chunks.push(r);
+            mag = q;
+        }
+        if self.neg { try!(f.write_str("-")) }
+        try!(write!(f, "{}", chunks.pop().unwrap()));
+        for c in chunks.iter().rev() { try!(write!(f, "{:09}", c)) }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseBigIntError;
+
+impl FromStr for BigInt {
+    type Err = ParseBigIntError;
+    fn from_str(s: &str) -> Result<BigInt, ParseBigIntError> {
+        let (neg, digits) = match s.as_bytes().first() {
+            Some(&b'-') => (true, &s[1..]),
+            Some(&b'+') => (false, &s[1..]),
+            _ => (false, s),
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(ParseBigIntError)
+        }
+        let mut mag = vec![];
+        // The first chunk is short, so the rest are 9 digits each.
+        let first = digits.len() % 9;
+        let mut start = 0;
+        for end in (if first == 0 { 9 } else { first } ..).step_by(9)
+            .take_while(|&e| e <= digits.len())
+        {
+            let chunk: u32 = digits[start..end].parse().unwrap();
+            let scale = 10u32.pow((end - start) as u32);
+            mag = mul_small_add(&mag, scale, chunk);
+            start = end;
+        }
+        Ok(BigInt::new(neg, mag))
+    }
+}
+
+// ---------- Operations on magnitudes ----------
+fn cmp_mag(a: &[u32], b: &[u32]) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (a, b) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut out = Vec::with_capacity(a.len() + 1);
+    let mut carry = 0u64;
+    for i in 0..a.len() {
+        let t = a[i] as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        out.push(t as u32);
+        carry = t >> 32;
+    }
+    out.push(carry as u32);
+    out
+}
+
+// requires a >= b.
+fn sub_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for i in 0..a.len() {
+        let t = a[i] as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        out.push(t as u32);
+        borrow = if t < 0 { 1 } else { 0 };
+    }
+    debug_assert!(borrow == 0);
+    out
+}
+
+fn mul_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = vec![0u32; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, &y) in b.iter().enumerate() {
+            let t = x as u64 * y as u64 + out[i+j] as u64 + carry;
+            out[i+j] = t as u32;
+            carry = t >> 32;
+        }
+        out[i + b.len()] = carry as u32;
+    }
+    out
+}
+
+fn mul_small_add(a: &[u32], m: u32, c: u32) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len() + 1);
+    let mut carry = c as u64;
+    for &x in a {
+        let t = x as u64 * m as u64 + carry;
+        out.push(t as u32);
+        carry = t >> 32;
+    }
+    out.push(carry as u32);
+    while out.last() == Some(&0) { out.pop(); }
+    out
+}
+
+fn div_rem_small(a: &[u32], d: u32) -> (Vec<u32>, u32) {
+    let mut q = vec![0u32; a.len()];
+    let mut r = 0u64;
+    for i in (0..a.len()).rev() {
+        let t = r << 32 | a[i] as u64;
+        q[i] = (t / d as u64) as u32;
+        r = t % d as u64;
+    }
+    while q.last() == Some(&0) { q.pop(); }
+    (q, r as u32)
+}
+
+// Shifts left by fewer than 32 bits, growing by one digit.
+fn shl_mag(a: &[u32], s: u32) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len() + 1);
+    let mut carry = 0u32;
+    for &x in a {
+        out.push(x << s | carry);
+        carry = if s == 0 { 0 } else { x >> (32 - s) };
+    }
+    out.push(carry);
+    out
+}
+
+// Knuth, TAOCP vol. 2, 4.3.1, algorithm D; after Hacker's Delight's divmnu.
+fn div_rem_mag(u: &[u32], v: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if cmp_mag(u, v) == Ordering::Less { return (vec![], u.to_vec()) }
+    if v.len() == 1 {
+        let (q, r) = div_rem_small(u, v[0]);
+        return (q, vec![r])
+    }
+    // Normalize so the divisor's top digit has its high bit set.
+    let s = v[v.len()-1].leading_zeros();
+    let mut vn = shl_mag(v, s);
+    vn.pop();
+    let mut un = shl_mag(u, s);
+    let (n, m) = (v.len(), u.len() - v.len());
+    let b = 1u64 << 32;
+    let mut q = vec![0u32; m + 1];
+    for j in (0..m+1).rev() {
+        let num = (un[j+n] as u64) << 32 | un[j+n-1] as u64;
+        let mut qhat = num / vn[n-1] as u64;
+        let mut rhat = num % vn[n-1] as u64;
+        while qhat >= b || qhat * vn[n-2] as u64 > (rhat << 32 | un[j+n-2] as u64) {
+            qhat -= 1;
+            rhat += vn[n-1] as u64;
+            if rhat >= b { break }
+        }
+        // Multiply and subtract.
+        let mut k = 0i64;
+        for i in 0..n {
+            let p = qhat * vn[i] as u64;
+            let t = un[i+j] as i64 - k - (p & 0xffffffff) as i64;
+            un[i+j] = t as u32;
+            k = (p >> 32) as i64 - (t >> 32);
+        }
+        let t = un[j+n] as i64 - k;
+        un[j+n] = t as u32;
+        q[j] = qhat as u32;
+        // We subtracted too much; add back.
+        if t < 0 {
+            q[j] = q[j].wrapping_sub(1);
+            let mut k = 0u64;
+            for i in 0..n {
+                let t = un[i+j] as u64 + vn[i] as u64 + k;
+                un[i+j] = t as u32;
+                k = t >> 32;
+            }
+            un[j+n] = un[j+n].wrapping_add(k as u32);
+        }
+    }
+    // Unnormalize the remainder.
+    let mut r: Vec<u32> = (0..n).map(|i| {
+        if s == 0 { un[i] } else { un[i] >> s | un[i+1] << (32 - s) }
+    }).collect();
+    while q.last() == Some(&0) { q.pop(); }
+    while r.last() == Some(&0) { r.pop(); }
+    (q, r)
+}
@@ -0,0 +1,366 @@
+// The byte encoding of code. Each instruction is an opcode byte followed by
+// its operands: variable indices, arities and pool indices as LEB128 varints,
+// prims as one byte, and jump targets as 16-bit little-endian byte offsets.
+// Jumps to offsets that don't fit in 16 bits use the wide forms of If and
+// Jump, whose targets are 32 bits. Literals and nested protos live in pools
+// beside the code.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::rc::Rc;
+
+use cam::*;
+use lang::*;
+
+pub const GET: u8 = 0;
+pub const SET: u8 = 1;
+pub const BOX_VAR: u8 = 2;
+pub const GET_BOX: u8 = 3;
+pub const SET_BOX: u8 = 4;
+pub const PUSH: u8 = 5;
+pub const POP: u8 = 6;
+pub const BIND: u8 = 7;
+pub const UNBIND: u8 = 8;
+pub const APPLY: u8 = 9;
+pub const TAIL_APPLY: u8 = 10;
+pub const PRIM: u8 = 11;
+pub const CLOSURE: u8 = 12;
+pub const IF: u8 = 13;
+pub const JUMP: u8 = 14;
+pub const RETURN: u8 = 15;
+pub const IF_WIDE: u8 = 16;
+pub const JUMP_WIDE: u8 = 17;
+
+#[inline]
+pub fn read_varint(code: &[u8], ip: &mut usize) -> u32 {
+    // Most operands fit in a byte.
+    let b = code[*ip];
+    if b < 0x80 { *ip += 1; return b as u32 }
+    let mut x = 0;
+    let mut shift = 0;
+    loop {
+        let b = code[*ip];
+        *ip += 1;
+        x |= ((b & 0x7f) as u32) << shift;
+        if b & 0x80 == 0 { return x }
+        shift += 7;
+    }
+}
+
+#[inline]
+pub fn read_u16(code: &[u8], ip: &mut usize) -> u16 {
+    let x = code[*ip] as u16 | (code[*ip + 1] as u16) << 8;
+    *ip += 2;
+    x
+}
+
+#[inline]
+pub fn read_u32(code: &[u8], ip: &mut usize) -> u32 {
+    let x = code[*ip] as u32 | (code[*ip + 1] as u32) << 8
+        | (code[*ip + 2] as u32) << 16 | (code[*ip + 3] as u32) << 24;
+    *ip += 4;
+    x
+}
+
+pub fn write_varint(code: &mut Vec<u8>, mut x: u32) {
+    while x >= 0x80 {
+        code.push(x as u8 | 0x80);
+        x >>= 7;
+    }
+    code.push(x as u8);
+}
+
+pub fn write_u16(code: &mut Vec<u8>, x: u16) {
+    code.push(x as u8);
+    code.push((x >> 8) as u8);
+}
+
+pub fn write_u32(code: &mut Vec<u8>, x: u32) {
+    for i in 0..4 { code.push((x >> (8 * i)) as u8) }
+}
+
+struct Assembler {
+    code: Vec<u8>,
+    consts: Vec<Lit>,
+    const_index: BTreeMap<Lit, u32>,
+    protos: Vec<Rc<Proto>>,
+}
+
+impl Assembler {
+    fn op(&mut self, op: u8, operand: u32) {
+        self.code.push(op);
+        write_varint(&mut self.code, operand);
+    }
+
+    fn constant(&mut self, l: &Lit) -> u32 {
+        if let Some(&i) = self.const_index.get(l) { return i }
+        let i = self.consts.len() as u32;
+        self.consts.push(l.clone());
+        self.const_index.insert(l.clone(), i);
+        i
+    }
+}
+
+// Encodes `instrs', with wide jumps where `wide' says, returning the
+// assembler and the offset of each instruction (and of the end).
+fn assemble_with(instrs: &[Instr], wide: &[bool]) -> (Assembler, Vec<usize>) {
+    let mut asm = Assembler { code: vec![], consts: vec![],
+                              const_index: BTreeMap::new(), protos: vec![] };
+    // Jump targets are instruction indices until every instruction's offset
+    // is known; we leave room for them and fill them in after.
+    let mut offsets = Vec::with_capacity(instrs.len() + 1);
+    let mut fixups = vec![];
+    for (instr, &wide) in instrs.iter().zip(wide) {
+        offsets.push(asm.code.len());
+        let width = if wide { 4 } else { 2 };
+        match *instr {
+            Instr::Get(i) => asm.op(GET, i),
+            Instr::Set(i) => asm.op(SET, i),
+            Instr::BoxVar(i) => asm.op(BOX_VAR, i),
+            Instr::GetBox(i) => asm.op(GET_BOX, i),
+            Instr::SetBox(i) => asm.op(SET_BOX, i),
+            Instr::Push(ref l) => { let i = asm.constant(l); asm.op(PUSH, i) }
+            Instr::Pop => asm.code.push(POP),
+            Instr::Bind(n) => asm.op(BIND, n),
+            Instr::Unbind(n) => asm.op(UNBIND, n),
+            Instr::Apply(n) => asm.op(APPLY, n),
+            Instr::TailApply(n) => asm.op(TAIL_APPLY, n),
+            Instr::Prim(p) => { asm.code.push(PRIM); asm.code.push(p as u8) }
+            Instr::Closure(ref proto) => {
+                let i = asm.protos.len() as u32;
+                asm.protos.push(proto.clone());
+                asm.op(CLOSURE, i)
+            }
+            Instr::If(thn, els) => {
+                asm.code.push(if wide { IF_WIDE } else { IF });
+                fixups.push((asm.code.len(), width, thn));
+                fixups.push((asm.code.len() + width, width, els));
+                asm.code.extend(&[0; 8][..2 * width]);
+            }
+            Instr::Jump(target) => {
+                asm.code.push(if wide { JUMP_WIDE } else { JUMP });
+                fixups.push((asm.code.len(), width, target));
+                asm.code.extend(&[0; 4][..width]);
+            }
+            Instr::Return => asm.code.push(RETURN),
+        }
+    }
+    offsets.push(asm.code.len());
+    for (at, width, target) in fixups {
+        // Narrow jumps to far targets get widened on the next pass.
+        let offset = offsets[target as usize];
+        for i in 0..width { asm.code[at + i] = (offset >> (8 * i)) as u8 }
+    }
+    (asm, offsets)
+}
+
+impl Proto {
+    pub fn assemble(arity: Arity, instrs: &[Instr]) -> Proto {
+        // Jumps start out narrow. Widening one moves the code after it, so
+        // may push other targets out of reach; we repeat until none are.
+        let mut wide = vec![false; instrs.len()];
+        loop {
+            let (asm, offsets) = assemble_with(instrs, &wide);
+            let mut widened = false;
+            for (i, instr) in instrs.iter().enumerate() {
+                let far = |t: InstrIndex| {
+                    offsets[t as usize] > u16::max_value() as usize
+                };
+                let needs_wide = match *instr {
+                    Instr::If(thn, els) => far(thn) || far(els),
+                    Instr::Jump(target) => far(target),
+                    _ => false,
+                };
+                if needs_wide && !wide[i] { wide[i] = true; widened = true }
+            }
+            if !widened {
+                return Proto { arity: arity, code: asm.code, consts: asm.consts,
+                               protos: asm.protos, debug: None }
+            }
+        }
+    }
+    // Decodes the instruction at `ip', advancing it past. Jump targets are
+    // left as byte offsets.
+    pub fn decode(&self, ip: &mut usize) -> Instr {
+        let code = &self.code;
+        let op = code[*ip];
+        *ip += 1;
+        match op {
+            GET => Instr::Get(read_varint(code, ip)),
+            SET => Instr::Set(read_varint(code, ip)),
+            BOX_VAR => Instr::BoxVar(read_varint(code, ip)),
+            GET_BOX => Instr::GetBox(read_varint(code, ip)),
+            SET_BOX => Instr::SetBox(read_varint(code, ip)),
+            PUSH => Instr::Push(self.consts[read_varint(code, ip) as usize]
+                                .clone()),
+            POP => Instr::Pop,
+            BIND => Instr::Bind(read_varint(code, ip)),
+            UNBIND => Instr::Unbind(read_varint(code, ip)),
+            APPLY => Instr::Apply(read_varint(code, ip)),
+            TAIL_APPLY => Instr::TailApply(read_varint(code, ip)),
+            PRIM => {
+                let p = Prim::from_index(code[*ip]).expect("bad prim");
+                *ip += 1;
+                Instr::Prim(p)
+            }
+            CLOSURE => Instr::Closure(self.protos[read_varint(code, ip) as usize]
+                                      .clone()),
+            IF => {
+                let thn = read_u16(code, ip) as InstrIndex;
+                Instr::If(thn, read_u16(code, ip) as InstrIndex)
+            }
+            JUMP => Instr::Jump(read_u16(code, ip) as InstrIndex),
+            IF_WIDE => {
+                let thn = read_u32(code, ip);
+                Instr::If(thn, read_u32(code, ip))
+            }
+            JUMP_WIDE => Instr::Jump(read_u32(code, ip)),
+            RETURN => Instr::Return,
+            _ => panic!("bad opcode {}", op),
+        }
+    }
+
+    // The index of the instruction containing byte `offset'.
+    pub fn instr_index(&self, offset: usize) -> Option<usize> {
+        let mut ip = 0;
+        let mut index = 0;
+        while ip < self.code.len() {
+            self.decode(&mut ip);
+            if offset < ip { return Some(index) }
+            index += 1;
+        }
+        None
+    }
+
+    // Decodes the whole of the code, with jump targets as instruction indices.
+    pub fn instrs(&self) -> Code {
+        let mut instrs = vec![];
+        let mut offsets = vec![];
+        let mut ip = 0;
+        while ip < self.code.len() {
+            offsets.push(ip);
+            instrs.push(self.decode(&mut ip));
+        }
+        offsets.push(ip);
+        let index = |offset: InstrIndex| {
+            offsets.binary_search(&(offset as usize)).expect("bad jump target")
+                as InstrIndex
+        };
+        for instr in &mut instrs {
+            *instr = match *instr {
+                Instr::If(thn, els) => Instr::If(index(thn), index(els)),
+                Instr::Jump(target) => Instr::Jump(index(target)),
+                _ => continue,
+            };
+        }
+        instrs
+    }
+}
+
+// Like read_varint, but fails on truncated or overlong varints.
+pub fn try_read_varint(code: &[u8], ip: &mut usize) -> Option<u32> {
+    let mut x: u32 = 0;
+    for shift in 0..5 {
+        let b = match code.get(*ip) { Some(&b) => b, None => return None };
+        *ip += 1;
+        let bits = (b & 0x7f) as u32;
+        if shift == 4 && bits > 0xf { return None }
+        x |= bits << (7 * shift);
+        if b & 0x80 == 0 { return Some(x) }
+    }
+    None
+}
+
+impl Proto {
+    // Replaces each prim operand `i' with `prim(i)', for code whose prims are
+    // numbered otherwise, as in a file. Fails if `prim' doesn't know one, or
+    // the code doesn't decode far enough to find them; what it does decode
+    // still needs checking.
+    pub fn map_prims<F>(&mut self, prim: F) -> Result<(), &'static str>
+        where F: Fn(u8) -> Option<Prim>
+    {
+        let code = &mut self.code;
+        let mut ip = 0;
+        while ip < code.len() {
+            let op = code[ip];
+            ip += 1;
+            match op {
+                POP | RETURN => {}
+                PRIM => match code.get(ip).and_then(|&i| prim(i)) {
+                    Some(p) => { code[ip] = p as u8; ip += 1 }
+                    None => return Err("unknown prim"),
+                },
+                JUMP => ip += 2,
+                IF | JUMP_WIDE => ip += 4,
+                IF_WIDE => ip += 8,
+                GET | SET | BOX_VAR | GET_BOX | SET_BOX | PUSH | BIND | UNBIND
+                    | APPLY | TAIL_APPLY | CLOSURE =>
+                    if try_read_varint(code, &mut ip).is_none() {
+                        return Err("bad operand")
+                    },
+                _ => return Err("unknown opcode"),
+            }
+        }
+        Ok(())
+    }
+
+    // Checks that the code can be decoded: that its opcodes and prims exist,
+    // its pool indices are in range and its jumps land on instructions.
+    // Code from elsewhere than the compiler must pass this before it's
+    // decoded or run.
+    pub fn check(&self) -> Result<(), &'static str> {
+        let code = &self.code;
+        let mut starts = vec![false; code.len() + 1];
+        let mut targets = vec![];
+        let mut ip = 0;
+        while ip < code.len() {
+            starts[ip] = true;
+            let op = code[ip];
+            ip += 1;
+            match op {
+                POP | RETURN => {}
+                PRIM => match code.get(ip).and_then(|&i| Prim::from_index(i)) {
+                    Some(_) => ip += 1,
+                    None => return Err("unknown prim"),
+                },
+                IF | JUMP | IF_WIDE | JUMP_WIDE => {
+                    let wide = op == IF_WIDE || op == JUMP_WIDE;
+                    let width = if wide { 4 } else { 2 };
+                    for _ in 0..(if op == IF || op == IF_WIDE { 2 } else { 1 }) {
+                        if ip + width > code.len() {
+                            return Err("truncated jump")
+                        }
+                        targets.push(if wide { read_u32(code, &mut ip) as usize }
+                                     else { read_u16(code, &mut ip) as usize });
+                    }
+                }
+                GET | SET | BOX_VAR | GET_BOX | SET_BOX | PUSH | BIND | UNBIND
+                    | APPLY | TAIL_APPLY | CLOSURE =>
+                {
+                    let x = match try_read_varint(code, &mut ip) {
+                        Some(x) => x as usize,
+                        None => return Err("bad operand"),
+                    };
+                    if op == PUSH && x >= self.consts.len() {
+                        return Err("literal out of range")
+                    }
+                    if op == CLOSURE && x >= self.protos.len() {
+                        return Err("proto out of range")
+                    }
+                }
+                _ => return Err("unknown opcode"),
+            }
+        }
+        starts[code.len()] = true;
+        if targets.iter().any(|&t| t >= starts.len() || !starts[t]) {
+            return Err("jump into the middle of an instruction")
+        }
+        for proto in &self.protos { try!(proto.check()) }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Proto {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(),fmt::Error> {
+        write!(f, "Proto {{ arity: {}, code: {:?} }}", self.arity, self.instrs())
+    }
+}
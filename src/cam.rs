@@ -1,37 +1,50 @@
-// TODO: a way to create and initialize a VM.
 use std::rc::Rc;
+use std::cell::RefCell;
 use std::mem;
 use std::cmp::Ordering;
+use std::fmt;
 
-pub type InstrIndex = u16;
+pub type InstrIndex = u32;
 
 use lang::*;
-use string::Str;
+use num;
+use prim::{self,PrimError};
+use sexp::Span;
+use trace::Tracer;
+use verify::{verify_in,VerifyError};
 
 #[derive(Clone,Debug)]
-pub enum Val { Lit(Lit), Func(Func) }
-type Env = Vec<Val>;
+pub enum Val {
+    Lit(Lit), Func(Func),
+    // Lists are built from pairs, ending in nil.
+    Pair(Rc<(Val, Val)>),
+    // A mutable variable that has been closed over. Never a user-visible
+    // value; only ever found in environments.
+    Ref(Rc<RefCell<Val>>),
+}
+pub type Env = Vec<Val>;
 
 impl Val {
     pub fn truthy(&self) -> bool {
         match self { &Val::Lit(ref l) => l.truthy(),
-                     &Val::Func(..) => true }
+                     _ => true }
     }
-    pub fn as_int(&self) -> i64 {
-        match *self { Val::Lit(Lit::Int(x)) => x,
-                      _ => panic!("non-integer value") }
+    pub fn cons(car: Val, cdr: Val) -> Val { Val::Pair(Rc::new((car, cdr))) }
+    pub fn list(elems: Vec<Val>) -> Val {
+        elems.into_iter().rev().fold(Val::Lit(Lit::Nil), |l, e| Val::cons(e, l))
     }
-    pub fn as_string(&self) -> Str {
-        match *self { Val::Lit(Lit::String(ref x)) => x.clone(),
-                      _ => panic!("non-string value") }
+    pub fn is_number(&self) -> bool {
+        match *self { Val::Lit(ref l) => num::is_number(l), _ => false }
     }
 }
 impl PartialEq for Val {
     fn eq(&self, other: &Val) -> bool {
         match (self, other) {
             (&Val::Lit(ref a), &Val::Lit(ref b)) => a == b,
-            // don't ask for equality on functions. just don't.
-            (&Val::Func(_), &Val::Func(_)) => unimplemented!(),
+            (&Val::Pair(ref a), &Val::Pair(ref b)) => a == b,
+            // Functions are only equal to themselves.
+            (&Val::Func(ref a), &Val::Func(ref b)) =>
+                Rc::ptr_eq(&a.proto, &b.proto) && Rc::ptr_eq(&a.env, &b.env),
             _ => false
         }
     }
@@ -46,40 +59,192 @@ impl PartialOrd for Val {
     }
 }
 
+impl fmt::Display for Val {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(),fmt::Error> {
+        match *self {
+            Val::Lit(ref l) => l.fmt(f),
+            Val::Func(_) => f.write_str("<function>"),
+            Val::Ref(ref r) => write!(f, "<box {}>", r.borrow()),
+            Val::Pair(ref p) => {
+                try!(write!(f, "({}", p.0));
+                let mut rest = &p.1;
+                loop {
+                    match *rest {
+                        Val::Pair(ref p) => { try!(write!(f, " {}", p.0));
+                                              rest = &p.1 }
+                        Val::Lit(Lit::Nil) => break,
+                        ref v => { try!(write!(f, " . {}", v)); break }
+                    }
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
 #[derive(Clone,Debug)]
 pub struct Func { proto: Rc<Proto>, env: Rc<Env> }
-#[derive(Debug)]
-pub struct Proto { pub code: Code, pub arity: Arity }
+// Code as the VM runs it; see bytecode.rs for the encoding.
+pub struct Proto {
+    pub arity: Arity,
+    pub code: Vec<u8>,
+    // The literals pushed and the functions closed over by the code.
+    pub consts: Vec<Lit>,
+    pub protos: Vec<Rc<Proto>>,
+    pub debug: Option<DebugInfo>,
+}
+
+// What the compiler knows about a proto's variables, for showing to people.
+// Instructions are referred to by index, as in Code.
+#[derive(Clone,Debug)]
+pub struct DebugInfo {
+    // What the function was bound to, if anything.
+    pub name: Option<Ident>,
+    // The variables in scope on entry, innermost last: those closed over,
+    // then the parameters.
+    pub scope: Vec<Ident>,
+    pub lets: Vec<LetRange>,
+    // Where the instructions that apply functions were written, by index.
+    pub spans: Vec<(usize, Span)>,
+}
+
+// Variables bound by a let, in scope from instruction `start' up to `end'.
+#[derive(Clone,Debug)]
+pub struct LetRange { pub ids: Vec<Ident>, pub start: usize, pub end: usize }
+
+impl DebugInfo {
+    // The names of the variables in scope at an instruction, innermost last.
+    pub fn names_at(&self, index: usize) -> Vec<Ident> {
+        let mut names = self.scope.clone();
+        // Lets are listed innermost first, since they finish first.
+        let mut lets: Vec<&LetRange> = self.lets.iter()
+            .filter(|l| l.start <= index && index < l.end).collect();
+        lets.sort_by_key(|l| l.start);
+        for l in lets { names.extend(l.ids.iter().cloned()) }
+        names
+    }
 
+    pub fn span_at(&self, index: usize) -> Option<Span> {
+        self.spans.binary_search_by_key(&index, |s| s.0).ok()
+            .map(|i| self.spans[i].1)
+    }
+}
+
+// Code as the compiler produces it, before encoding.
 pub type Code = Vec<Instr>;
 #[derive(Debug)]
 pub enum Instr {
     Get(VarIndex),
-    Push(Lit),
+    Set(VarIndex),
+    // Moves a variable into a fresh box, for mutable captured variables.
+    BoxVar(VarIndex), GetBox(VarIndex), SetBox(VarIndex),
+    Push(Lit), Pop,
+    // Move values from the stack into new variables, for let; and drop them.
+    Bind(VarIndex), Unbind(VarIndex),
     Apply(Arity), TailApply(Arity),
+    // Applies a prim to its arguments atop the stack.
+    Prim(Prim),
     Closure(Rc<Proto>),
     If(InstrIndex, InstrIndex),
     Jump(InstrIndex),
     Return,
-    // Let, // optimization
-    // Prim(Prim), // optimization
 }
-// 2015-09-04
-// Instr size = 24, align = 8
-// Lit   size = 16
-// need to break up Push() into separate instrs.
 
 #[derive(Debug)]
+pub enum RuntimeError {
+    Verify(VerifyError),
+    Prim(PrimError),
+    NotAFunction(Val),
+    WrongArity { expected: Arity, got: Arity },
+    // Something the verifier should have caught, or stepping a finished VM.
+    BadCode(&'static str),
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(),fmt::Error> {
+        match *self {
+            RuntimeError::Verify(ref e) => write!(f, "bad code: {}", e),
+            RuntimeError::Prim(ref e) => e.fmt(f),
+            RuntimeError::NotAFunction(ref v) =>
+                write!(f, "applying non-function: {}", v),
+            RuntimeError::WrongArity { expected, got } =>
+                write!(f, "function takes {} argument{}, got {}", expected,
+                       if expected == 1 { "" } else { "s" }, got),
+            RuntimeError::BadCode(what) => write!(f, "bad code: {}", what),
+        }
+    }
+}
+
+impl ::std::error::Error for RuntimeError {}
+
+impl From<PrimError> for RuntimeError {
+    fn from(e: PrimError) -> RuntimeError { RuntimeError::Prim(e) }
+}
+
+pub type RunResult<A> = Result<A, RuntimeError>;
+
+// A frame in a backtrace.
+#[derive(Clone,Debug)]
+pub struct TraceFrame {
+    // The name the function was bound to, if known.
+    pub name: Option<Ident>,
+    // Where the frame's current instruction came from, if known.
+    pub span: Option<Span>,
+    // How many of the frame's callers were dropped by tail calls.
+    pub elided: usize,
+}
+
+impl TraceFrame {
+    // `src' should be the text the program was compiled from.
+    pub fn render(&self, src: &str) -> String {
+        let name = self.name.as_ref().map_or("<fn>", |n| &**n);
+        let mut out = match self.span {
+            Some(span) => {
+                let (line, col) = span.line_col(src);
+                format!("  in {} at {}:{}\n", name, line, col)
+            }
+            None => format!("  in {}\n", name),
+        };
+        if self.elided > 0 {
+            out.push_str(&format!("  ... {} frame{} elided by tail calls\n",
+                                  self.elided,
+                                  if self.elided == 1 { "" } else { "s" }))
+        }
+        out
+    }
+}
+
+// A frame, for debuggers to look at.
+pub struct FrameView {
+    pub proto: Rc<Proto>,
+    // The index of the frame's current instruction, if it has one.
+    pub index: Option<usize>,
+    // Its variables, innermost last. Those in boxes are still in them.
+    pub env: Env,
+    pub elided: usize,
+}
+
 pub struct VM {
     stack: Vec<Val>,
     frame: Frame,
-    frames: Vec<Frame>
+    frames: Vec<Frame>,
+    // Told what we do, if present; see trace::Tracer.
+    tracer: Option<Box<dyn Tracer>>,
+}
+impl fmt::Debug for VM {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("VM").field("stack", &self.stack)
+            .field("frame", &self.frame).field("frames", &self.frames)
+            .finish()
+    }
 }
 #[derive(Debug)]
 struct Frame {
     proto: Rc<Proto>,
     ip: usize,
     env: FrameEnv,
+    // How many frames tail calls have dropped on the way to this one.
+    elided: usize,
 }
 #[derive(Debug)]
 struct FrameEnv {
@@ -91,13 +256,40 @@ struct FrameEnv {
 }
 
 impl FrameEnv {
-    fn access(&self, index: VarIndex) -> Val {
+    fn access(&self, index: VarIndex) -> RunResult<Val> {
+        let i = 1 + index as usize;
+        let u_len = self.unique.len();
+        if i <= u_len { return Ok(self.unique[u_len - i].clone()) }
+        let len = u_len + self.shared.len();
+        if i > len { return Err(RuntimeError::BadCode("unbound variable")) }
+        Ok(self.shared[len - i].clone())
+    }
+    fn set(&mut self, index: VarIndex, val: Val) -> RunResult<()> {
         let i = 1 + index as usize;
         let u_len = self.unique.len();
-        if i <= u_len { return self.unique[u_len - i].clone() }
+        if i <= u_len { self.unique[u_len - i] = val; return Ok(()) }
+        let len = u_len + self.shared.len();
+        if i > len { return Err(RuntimeError::BadCode("unbound variable")) }
+        // Any closure sharing our env can't refer to this variable, or it would
+        // have been boxed; so copy-on-write is safe.
+        Rc::make_mut(&mut self.shared)[len - i] = val;
+        Ok(())
+    }
+    fn access_box(&self, index: VarIndex) -> RunResult<Rc<RefCell<Val>>> {
+        match try!(self.access(index)) {
+            Val::Ref(r) => Ok(r),
+            _ => Err(RuntimeError::BadCode("unboxed variable")),
+        }
+    }
+    fn unbind(&mut self, n: usize) -> RunResult<()> {
+        let u_len = self.unique.len();
+        if n <= u_len { self.unique.truncate(u_len - n); return Ok(()) }
+        // Some of them have been closed over; copy-on-write again.
         let len = u_len + self.shared.len();
-        assert!(i <= len);
-        self.shared[len - i].clone()
+        if n > len { return Err(RuntimeError::BadCode("unbound variable")) }
+        self.unique.clear();
+        Rc::make_mut(&mut self.shared).truncate(len - n);
+        Ok(())
     }
     fn close(&mut self) -> Rc<Env> {
         if self.unique.is_empty() { return self.shared.clone() }
@@ -110,105 +302,263 @@ impl FrameEnv {
 }
 
 impl VM {
-    pub fn run(code: Code) -> Val {
-        let mut vm = VM {
+    // The VM trusts its code, so it must decode and pass the verifier first.
+    pub fn new(proto: Proto) -> RunResult<VM> {
+        VM::new_in(proto, vec![])
+    }
+
+    // Runs `proto' as if closed over `env', innermost last.
+    pub fn new_in(proto: Proto, env: Env) -> RunResult<VM> {
+        if proto.arity != 0 {
+            return Err(RuntimeError::WrongArity { expected: proto.arity,
+                                                  got: 0 })
+        }
+        try!(proto.check().map_err(RuntimeError::BadCode));
+        try!(verify_in(&proto, env.len()).map_err(RuntimeError::Verify));
+        Ok(VM {
             stack: vec![],
             frames: vec![],
             frame: Frame {
-                proto: Rc::new(Proto{code: code, arity: 0}),
+                proto: Rc::new(proto),
                 ip: 0,
-                env: FrameEnv{shared: Rc::new(vec![]), unique: vec![]}
+                env: FrameEnv{shared: Rc::new(env), unique: vec![]},
+                elided: 0,
+            },
+            tracer: None,
+        })
+    }
+
+    pub fn run(proto: Proto) -> RunResult<Val> {
+        let mut vm = try!(VM::new(proto));
+        try!(vm.finish());
+        Ok(vm.value())
+    }
+
+    // Runs until done, or until an instruction fails.
+    pub fn finish(&mut self) -> RunResult<()> {
+        while !self.done() { try!(self.step()) }
+        Ok(())
+    }
+
+    // The calls in progress, innermost first. Suspended frames are at the
+    // call they made; the current one at its next instruction, which after
+    // an error is the one that failed.
+    pub fn backtrace(&self) -> Vec<TraceFrame> {
+        self.frames().iter().map(|f| {
+            let debug = f.proto.debug.as_ref();
+            TraceFrame {
+                name: debug.and_then(|d| d.name.clone()),
+                span: debug.and_then(|d| f.index.and_then(|i| d.span_at(i))),
+                elided: f.elided,
             }
+        }).collect()
+    }
+
+    // The frames, innermost first, as backtrace describes them.
+    pub fn frames(&self) -> Vec<FrameView> {
+        let view = |f: &Frame, offset| {
+            let mut env = (*f.env.shared).clone();
+            env.extend(f.env.unique.iter().cloned());
+            FrameView { proto: f.proto.clone(),
+                        index: f.proto.instr_index(offset), env: env,
+                        elided: f.elided }
         };
-        while !vm.done() { vm.step() }
-        vm.value()
+        let current = view(&self.frame, self.frame.ip);
+        // A suspended frame's ip is just past its call.
+        let suspended = self.frames.iter().rev().map(|f| view(f, f.ip - 1));
+        Some(current).into_iter().chain(suspended).collect()
+    }
+
+    // The current frame's proto and the byte offset of its next instruction.
+    pub fn location(&self) -> (&Rc<Proto>, usize) {
+        (&self.frame.proto, self.frame.ip)
     }
 
-    // only to be used within `run'
-    fn done(&self) -> bool {
-        // TODO: could do this more effectively with frames trickery.
-        self.frames.is_empty() &&
-            (self.frame.ip == self.frame.proto.code.len()
-             || match self.frame.proto.code[self.frame.ip] {
-                Instr::Return | Instr::TailApply(..) => true,
-                _ => false
-            })
+    // Returning from the outermost frame runs off the end of its code.
+    pub fn done(&self) -> bool {
+        self.frames.is_empty() && self.frame.ip == self.frame.proto.code.len()
     }
 
-    fn value(mut self) -> Val {
+    // Reports what the VM does to `tracer' from now on, or stops reporting.
+    // Returns the tracer it replaces.
+    pub fn set_tracer(&mut self, tracer: Option<Box<dyn Tracer>>)
+                      -> Option<Box<dyn Tracer>>
+    {
+        mem::replace(&mut self.tracer, tracer)
+    }
+
+    // Tells the tracer, if any, about something. It's taken out while it
+    // looks, so it can see the VM.
+    #[inline]
+    fn trace<F: FnOnce(&mut dyn Tracer, &VM)>(&mut self, f: F) {
+        if let Some(mut tracer) = self.tracer.take() {
+            f(&mut *tracer, self);
+            self.tracer = Some(tracer)
+        }
+    }
+
+    // The number of suspended frames, i.e. non-tail calls in progress.
+    pub fn depth(&self) -> usize { self.frames.len() }
+
+    pub fn stack(&self) -> &[Val] { &self.stack }
+
+    // The byte offset of the next instruction in the current frame.
+    pub fn ip(&self) -> usize { self.frame.ip }
+
+    // The result, once done.
+    pub fn value(mut self) -> Val {
         debug_assert!(self.stack.len() == 1);
         self.stack.pop().unwrap()
     }
 
-    pub fn step(&mut self) {
-        use self::Instr::*;
+    // Runs one instruction. If it fails, the VM is left as it was before it,
+    // so the error can be looked into.
+    pub fn step(&mut self) -> RunResult<()> {
+        let ip = self.frame.ip;
+        let r = self.exec();
+        if r.is_err() { self.frame.ip = ip }
+        r
+    }
+
+    fn pop(&mut self) -> RunResult<Val> {
+        self.stack.pop().ok_or(RuntimeError::BadCode("stack underflow"))
+    }
+
+    fn exec(&mut self) -> RunResult<()> {
+        use bytecode::*;
 
         // avoids borrowing complications at the expense of a refcount bump.
         let proto = self.frame.proto.clone();
-        let ip = self.frame.ip;
-        self.frame.ip += 1;
-        assert!(ip < proto.code.len());
-
-        if ::DEBUG {
-            println!(" instr:  {:?}
- stack:  {:?}
- env:    {:?}
- frames: {:?}
-",
-                     proto.code[ip],
-                     self.stack,
-                     self.frame.env,
-                     self.frames)
-        }
+        let code = &proto.code;
+        let mut ip = self.frame.ip;
+        if ip >= code.len() { return Err(RuntimeError::BadCode("ran off the end")) }
+
+        self.trace(|t, vm| t.instr(vm));
 
-        match proto.code[ip] {
-            Get(i) => { let val = self.frame.env.access(i);
-                        self.stack.push(val) }
-            Push(ref l) => self.stack.push(Val::Lit(l.clone())),
-            Closure(ref proto) =>
+        let op = code[ip];
+        ip += 1;
+        // Jumps and calls overwrite this.
+        let operand = match op {
+            POP | RETURN | PRIM | IF | JUMP | IF_WIDE | JUMP_WIDE => 0,
+            _ => read_varint(code, &mut ip),
+        };
+        self.frame.ip = ip;
+
+        match op {
+            GET => { let val = try!(self.frame.env.access(operand));
+                     self.stack.push(val) }
+            SET => { let val = try!(self.pop());
+                     try!(self.frame.env.set(operand, val)) }
+            BOX_VAR => {
+                let val = try!(self.frame.env.access(operand));
+                try!(self.frame.env.set(operand,
+                                        Val::Ref(Rc::new(RefCell::new(val)))))
+            }
+            GET_BOX => {
+                let val = try!(self.frame.env.access_box(operand)).borrow().clone();
+                self.stack.push(val)
+            }
+            SET_BOX => {
+                let val = try!(self.pop());
+                *try!(self.frame.env.access_box(operand)).borrow_mut() = val
+            }
+            PUSH =>
+                self.stack.push(Val::Lit(proto.consts[operand as usize].clone())),
+            POP => { try!(self.pop()); }
+            BIND => {
+                if operand as usize > self.stack.len() {
+                    return Err(RuntimeError::BadCode("stack underflow"))
+                }
+                let at = self.stack.len() - operand as usize;
+                self.frame.env.unique.extend(self.stack.drain(at..))
+            }
+            UNBIND => try!(self.frame.env.unbind(operand as usize)),
+            CLOSURE =>
                 self.stack.push(Val::Func(Func {
-                        proto: proto.clone(),
+                        proto: proto.protos[operand as usize].clone(),
                         env: self.frame.env.close() })),
-            If(thn, els) =>
-                (self.frame.ip =
-                    if self.stack.pop().unwrap().truthy() { thn }
-                    else { els } as usize),
-            Jump(index) => (self.frame.ip = index as usize),
-            Apply(arity) => self.apply(arity, false),
-            TailApply(arity) => self.apply(arity, true),
-            Return => self.ret(),
+            IF => {
+                let thn = read_u16(code, &mut ip);
+                let els = read_u16(code, &mut ip);
+                self.frame.ip =
+                    if try!(self.pop()).truthy() { thn } else { els } as usize
+            }
+            JUMP => self.frame.ip = read_u16(code, &mut ip) as usize,
+            IF_WIDE => {
+                let thn = read_u32(code, &mut ip);
+                let els = read_u32(code, &mut ip);
+                self.frame.ip =
+                    if try!(self.pop()).truthy() { thn } else { els } as usize
+            }
+            JUMP_WIDE => self.frame.ip = read_u32(code, &mut ip) as usize,
+            APPLY => try!(self.apply(operand, false)),
+            TAIL_APPLY => try!(self.apply(operand, true)),
+            PRIM => {
+                let prim = try!(Prim::from_index(code[ip])
+                                .ok_or(RuntimeError::BadCode("unknown prim")));
+                self.frame.ip += 1;
+                if prim.arity() as usize > self.stack.len() {
+                    return Err(RuntimeError::BadCode("stack underflow"))
+                }
+                let at = self.stack.len() - prim.arity() as usize;
+                let val = try!(prim::apply(prim, &self.stack[at..]));
+                self.trace(|t, vm| t.prim(vm, prim, &vm.stack[at..], &val));
+                self.stack.truncate(at);
+                self.stack.push(val);
+            }
+            RETURN => self.ret(),
+            _ => return Err(RuntimeError::BadCode("unknown opcode")),
         }
+        Ok(())
     }
 
-    fn apply(&mut self, arity: Arity, tail: bool) {
+    fn apply(&mut self, arity: Arity, tail: bool) -> RunResult<()> {
         let num_vals = 1 + arity as usize;
-        assert!(num_vals <= self.stack.len());
+        if num_vals > self.stack.len() {
+            return Err(RuntimeError::BadCode("stack underflow"))
+        }
         let func_idx = self.stack.len() - num_vals;
         let func = self.stack[func_idx].clone();
         match func {
             Val::Func(f) => self.call(f, arity, func_idx, tail),
             Val::Lit(Lit::Prim(prim)) => {
-                let val = self.prim(prim, &self.stack[func_idx+1..]);
+                if arity != prim.arity() {
+                    return Err(RuntimeError::WrongArity {
+                        expected: prim.arity(), got: arity })
+                }
+                let val = try!(prim::apply(prim, &self.stack[func_idx+1..]));
+                self.trace(|t, vm| t.prim(vm, prim, &vm.stack[func_idx+1..],
+                                          &val));
                 self.stack.truncate(func_idx);
                 self.stack.push(val);
+                // Prims don't get a frame, so we return on their behalf.
+                if tail { self.ret() }
+                Ok(())
             }
-            _ => panic!("applying non-function"),
+            func => Err(RuntimeError::NotAFunction(func)),
         }
     }
 
     #[inline]
-    fn call(&mut self, func: Func, arity: Arity, func_idx: usize, tail: bool) {
+    fn call(&mut self, func: Func, arity: Arity, func_idx: usize, tail: bool)
+            -> RunResult<()>
+    {
         debug_assert!(arity as usize == self.stack.len() - func_idx - 1);
         if arity != func.proto.arity {
-            panic!("wrong number of arguments to function")
+            return Err(RuntimeError::WrongArity { expected: func.proto.arity,
+                                                  got: arity })
         }
+        self.trace(|t, vm| {
+            t.call(vm, &func.proto, &vm.stack[func_idx+1..], tail)
+        });
         let new_frame = Frame {
             proto: func.proto.clone(),
             ip: 0,
             env: FrameEnv {
                 shared: func.env.clone(),
                 unique: self.stack[func_idx+1..].to_vec(),
-            }
+            },
+            elided: if tail { self.frame.elided + 1 } else { 0 },
         };
         self.stack.truncate(func_idx);
         if !tail {
@@ -217,31 +567,17 @@ impl VM {
             // Drop our old frame.
             self.frame = new_frame;
         }
-    }
-
-    #[inline]
-    fn prim(&self, prim: Prim, args: &[Val]) -> Val {
-        if args.len() != prim.arity() as usize {
-            panic!("applying prim to wrong # of arguments")
-        }
-        Val::Lit(match prim {
-                Equal => Lit::Bool(args[0] == args[1]),
-                // NB. uses PartialOrd :(
-                Leq => Lit::Bool(args[0] <= args[1]),
-                Add => Lit::Int(args[0].as_int() + args[1].as_int()),
-                Sub => Lit::Int(args[0].as_int() - args[1].as_int()),
-                Mul => Lit::Int(args[0].as_int() * args[1].as_int()),
-                Div => Lit::Int(args[0].as_int() / args[1].as_int()),
-                Print => {
-                    println!("{}", args[0].as_string());
-                    Lit::Nil
-                }
-            })
+        Ok(())
     }
 
     fn ret(&mut self) {
-        debug_assert!(!self.frames.is_empty());
-        self.frame = self.frames.pop().unwrap();
-        // do we need to advance ip? no.
+        self.trace(|t, vm| if let Some(val) = vm.stack.last() {
+            t.ret(vm, val)
+        });
+        match self.frames.pop() {
+            // do we need to advance ip? no.
+            Some(frame) => self.frame = frame,
+            None => self.frame.ip = self.frame.proto.code.len(),
+        }
     }
 }
@@ -2,57 +2,156 @@ use std::rc::Rc;
 
 use lang::*;
 use cam::*;
+use sexp::Span;
+use string::Symbol;
 
-pub fn compile(e: &Exp) -> Vec<Instr> {
-    let mut s = State { instrs: vec![] };
-    s.compile(e);
-    s.instrs.push(Instr::Return);
-    s.instrs
+pub fn compile(e: &Exp) -> Proto {
+    compile_proto(Some(Symbol::intern(TOP_LEVEL)), vec![], &[], e)
+}
+
+// Compiles `e' to be run with the variables `ids' bound, innermost last; see
+// VM::new_in.
+pub fn compile_in(e: &Exp, ids: &[Ident]) -> Proto {
+    let scope = ids.iter().map(|id| (id.clone(), false)).collect();
+    compile_proto(Some(Symbol::intern(TOP_LEVEL)), scope, &[], e)
+}
+
+// The name of the proto for the program as a whole.
+pub const TOP_LEVEL: &'static str = "<top>";
+
+// Compiles the body of a function, which binds `ids' on top of the
+// variables in `scope'.
+fn compile_proto(name: Option<Ident>, scope: Vec<(Ident, bool)>, ids: &[Ident],
+                 body: &Exp) -> Proto
+{
+    let mut s = State { instrs: vec![], scope: scope, lets: vec![],
+                        spans: vec![] };
+    s.bind(ids, body);
+    s.compile(body, true);
+    let mut proto = Proto::assemble(ids.len() as Arity, &s.instrs);
+    proto.debug = Some(DebugInfo {
+        name: name,
+        scope: s.scope.into_iter().map(|v| v.0).collect(),
+        lets: s.lets,
+        spans: s.spans,
+    });
+    proto
 }
 
 struct State {
     instrs: Vec<Instr>,
+    // for each variable in scope, innermost last: its name, and does it live
+    // in a box?
+    scope: Vec<(Ident, bool)>,
+    lets: Vec<LetRange>,
+    spans: Vec<(usize, Span)>,
 }
 
 impl State {
-    fn compile(&mut self, e: &Exp) {
+    fn is_boxed(&self, index: VarIndex) -> bool {
+        self.scope[self.scope.len() - 1 - index as usize].1
+    }
+
+    // Brings the innermost variables of the frame, named `ids', into scope for
+    // `body', boxing those that need it.
+    fn bind(&mut self, ids: &[Ident], body: &Exp) {
+        let n = ids.len() as VarIndex;
+        for (i, id) in ids.iter().enumerate() {
+            self.scope.push((id.clone(), needs_box(body, n - 1 - i as VarIndex)));
+        }
+        for i in 0..n {
+            if self.is_boxed(i) { self.instrs.push(Instr::BoxVar(i)) }
+        }
+    }
+
+    // Functions take the name of the variable they're bound to.
+    fn compile_bound(&mut self, name: &Ident, e: &Exp) {
+        match *e {
+            Exp::Lam(ref ids, ref body) => {
+                let proto = compile_proto(Some(name.clone()), self.scope.clone(),
+                                          ids, body);
+                self.instrs.push(Instr::Closure(Rc::new(proto)));
+            }
+            _ => self.compile(e, false),
+        }
+    }
+
+    // An expression in tail position is the last thing its function does, so
+    // it returns (or tail-calls) itself instead of falling through.
+    fn compile(&mut self, e: &Exp, tail: bool) {
         use cam::Instr::*;
         match *e {
             Exp::Lit(ref l) => self.instrs.push(Push(l.clone())),
-            Exp::Var(_, index) => self.instrs.push(Get(index)),
+            Exp::Var(_, index) => self.instrs.push(
+                if self.is_boxed(index) { GetBox(index) } else { Get(index) }),
+            Exp::Set(ref name, index, ref exp) => {
+                self.compile_bound(name, exp);
+                self.instrs.push(
+                    if self.is_boxed(index) { SetBox(index) }
+                    else { Set(index) });
+                self.instrs.push(Push(Lit::Nil));
+            }
             Exp::Lam(ref ids, ref body) => {
-                // FIXME: variable binding order needs to be reversed!
-                // or maybe it needs to be reversed in the parser.
-                // I'm not sure.
-                let proto = Proto { code: compile(body),
-                                    arity: ids.len() as Arity };
+                let proto = compile_proto(None, self.scope.clone(), ids, body);
                 self.instrs.push(Closure(Rc::new(proto)));
             }
-            Exp::App(ref func, ref args) => {
-                self.compile(func);
-                for arg in args { self.compile(arg) }
-                self.instrs.push(Apply(args.len() as Arity))
-            }
+            // Known prims applied to the right number of arguments needn't
+            // be pushed as values.
+            Exp::App(ref func, ref args, span) => match saturated_prim(func, args) {
+                Some(p) => {
+                    for arg in args { self.compile(arg, false) }
+                    self.spans.push((self.instrs.len(), span));
+                    self.instrs.push(Prim(p))
+                }
+                None => {
+                    self.compile(func, false);
+                    for arg in args { self.compile(arg, false) }
+                    let arity = args.len() as Arity;
+                    self.spans.push((self.instrs.len(), span));
+                    self.instrs.push(if tail { TailApply(arity) }
+                                     else { Apply(arity) });
+                    return
+                }
+            },
             Exp::Let(ref binds, ref body) => {
-                // TODO: better compilation strategy here.
-                let proto = Proto { code: compile(body),
-                                    arity: binds.len() as Arity };
-                self.instrs.push(Closure(Rc::new(proto)));
-                for &(_, ref exp) in binds { self.compile(exp) }
-                self.instrs.push(Apply(binds.len() as Arity))
+                // Let-bound variables live in the current frame.
+                for &(ref id, ref exp) in binds { self.compile_bound(id, exp) }
+                let n = binds.len() as VarIndex;
+                if n == 0 { return self.compile(body, tail) }
+                self.instrs.push(Bind(n));
+                let ids: Vec<Ident> = binds.iter().map(|b| b.0.clone())
+                                           .collect();
+                let start = self.instrs.len();
+                self.bind(&ids, body);
+                self.compile(body, tail);
+                self.lets.push(LetRange { ids: ids, start: start,
+                                          end: self.instrs.len() });
+                let len = self.scope.len();
+                self.scope.truncate(len - n as usize);
+                // In tail position, returning drops the whole frame anyway.
+                if !tail { self.instrs.push(Unbind(n)) }
+                return
             }
             Exp::If(ref subject, ref thn, ref els) => {
-                self.compile(subject);
+                self.compile(subject, false);
 
                 // We push an If with dummy addresses and fix it up later.
                 let if_index = self.instrs.len();
                 self.instrs.push(If(0, 0));
                 let thn_index = self.instrs.len() as InstrIndex;
-                self.compile(thn);
+                self.compile(thn, tail);
+                // In tail position, both branches return, so there's nothing
+                // to join.
+                if tail {
+                    let els_index = self.instrs.len() as InstrIndex;
+                    self.compile(els, tail);
+                    self.instrs[if_index] = If(thn_index, els_index);
+                    return
+                }
                 let jmp_index = self.instrs.len();
                 self.instrs.push(Jump(0)); // again, we fix it up later
                 let els_index = self.instrs.len() as InstrIndex;
-                self.compile(els);
+                self.compile(els, tail);
                 let join_index = self.instrs.len() as InstrIndex;
 
                 // Fix up our jumps.
@@ -60,5 +159,60 @@ impl State {
                 self.instrs[jmp_index] = Jump(join_index);
             }
         }
+        if tail { self.instrs.push(Return) }
+    }
+}
+
+fn saturated_prim(func: &Exp, args: &[Exp]) -> Option<Prim> {
+    match *func {
+        Exp::Lit(Lit::Prim(p)) if p.arity() as usize == args.len() => Some(p),
+        _ => None,
+    }
+}
+
+// ---------- Deciding which variables to box ----------
+// Closing over an environment copies it, so a variable that is both mutated
+// (by set!) and captured (used from a closure) must live in a shared box.
+// Variables that are only mutated can be updated in place in their frame.
+//
+// `var' is the DeBruijn index of the variable as seen from `body'.
+fn needs_box(body: &Exp, var: VarIndex) -> bool {
+    let mut usage = Usage { mutated: false, captured: false };
+    usage.scan(body, var, false);
+    usage.mutated && usage.captured
+}
+
+struct Usage { mutated: bool, captured: bool }
+
+impl Usage {
+    fn scan(&mut self, e: &Exp, var: VarIndex, in_closure: bool) {
+        match *e {
+            Exp::Lit(_) => {}
+            Exp::Var(_, index) => {
+                if index == var && in_closure { self.captured = true }
+            }
+            Exp::Set(_, index, ref exp) => {
+                if index == var {
+                    self.mutated = true;
+                    if in_closure { self.captured = true }
+                }
+                self.scan(exp, var, in_closure)
+            }
+            Exp::Lam(ref ids, ref body) =>
+                self.scan(body, var + ids.len() as VarIndex, true),
+            Exp::App(ref func, ref args, _) => {
+                self.scan(func, var, in_closure);
+                for arg in args { self.scan(arg, var, in_closure) }
+            }
+            Exp::Let(ref binds, ref body) => {
+                for &(_, ref exp) in binds { self.scan(exp, var, in_closure) }
+                self.scan(body, var + binds.len() as VarIndex, in_closure)
+            }
+            Exp::If(ref subject, ref thn, ref els) => {
+                self.scan(subject, var, in_closure);
+                self.scan(thn, var, in_closure);
+                self.scan(els, var, in_closure);
+            }
+        }
     }
 }
@@ -0,0 +1,243 @@
+// An interactive debugger over the VM. It reads commands a line at a time:
+//
+//   step, s          run one instruction
+//   next, n          run one instruction, stepping over any call it makes
+//   out, o           run until the current function returns
+//   continue, c      run until a breakpoint, an error or the end
+//   break LINE       stop at calls written on a line of the source
+//   break NAME       stop on entering functions bound to NAME
+//   break pN         stop on entering the function disassembled as pN
+//   clear            remove all breakpoints
+//   stack            show the operand stack, top last
+//   env [N]          show the variables of frame N (default 0, the current)
+//   frames, bt       show the frames, innermost first
+//   print EXP, p     evaluate EXP with the current frame's variables
+//   quit, q          stop debugging
+//
+// After an error the VM stays where it failed, so it can still be looked at.
+use std::io::{self,BufRead,Write};
+use std::rc::Rc;
+use std::str::FromStr;
+
+use cam::*;
+use compile::compile_in;
+use disasm::{protos,show_instr};
+use lang::*;
+use sexp::Sexp;
+use string::Symbol;
+
+pub struct Debugger<'a> {
+    vm: VM,
+    // The program's outermost proto, which the others are reachable from.
+    root: Rc<Proto>,
+    // The text the program was compiled from, for finding lines.
+    src: &'a str,
+    breakpoints: Vec<Breakpoint>,
+}
+
+// Protos are compared by address; `root' keeps them alive.
+enum Breakpoint { Line(usize), Proto(Ident), Id(*const Proto) }
+
+// Why we stopped running.
+enum Stop { Paused, Done, Failed(RuntimeError) }
+
+impl<'a> Debugger<'a> {
+    pub fn new(vm: VM, src: &'a str) -> Debugger<'a> {
+        let root = vm.location().0.clone();
+        Debugger { vm: vm, root: root, src: src, breakpoints: vec![] }
+    }
+
+    // Reads commands from `input' until it runs out, or the program
+    // finishes, returning the program's value if it did.
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, out: &mut W)
+                                     -> io::Result<Option<Val>>
+    {
+        try!(self.show_location(out));
+        let mut lines = input.lines();
+        loop {
+            try!(write!(out, "(debug) "));
+            try!(out.flush());
+            let line = match lines.next() {
+                Some(line) => try!(line),
+                None => return Ok(None),
+            };
+            let line = line.trim();
+            let (cmd, arg) = match line.find(' ') {
+                Some(i) => (&line[..i], line[i..].trim()),
+                None => (line, ""),
+            };
+            let depth = self.vm.depth();
+            let stop = match cmd {
+                "" => continue,
+                "step" | "s" => self.resume(|_| true),
+                "next" | "n" => self.resume(|vm| vm.depth() <= depth),
+                "out" | "o" => self.resume(|vm| vm.depth() < depth),
+                "continue" | "c" => self.resume(|_| false),
+                "quit" | "q" => return Ok(None),
+                _ => { try!(self.command(cmd, arg, out)); continue }
+            };
+            match stop {
+                Stop::Paused => try!(self.show_location(out)),
+                Stop::Done => {
+                    let val = self.vm.stack().last().cloned();
+                    if let Some(ref val) = val {
+                        try!(writeln!(out, "finished: {}", val))
+                    }
+                    return Ok(val)
+                }
+                Stop::Failed(e) => {
+                    try!(writeln!(out, "error: {}", e));
+                    try!(self.show_frames(out));
+                }
+            }
+        }
+    }
+
+    // Commands that look around without running anything.
+    fn command<W: Write>(&mut self, cmd: &str, arg: &str, out: &mut W)
+                         -> io::Result<()>
+    {
+        match cmd {
+            "break" | "b" => {
+                let bp = match self.breakpoint(arg) {
+                    Ok(bp) => bp,
+                    Err(e) => return writeln!(out, "{}", e),
+                };
+                if let Breakpoint::Line(line) = bp {
+                    if !self.has_calls(line) {
+                        try!(writeln!(out, "warning: no calls on line {} to \
+                                            stop at", line))
+                    }
+                }
+                self.breakpoints.push(bp);
+                writeln!(out, "breakpoint {}", self.breakpoints.len())
+            }
+            "clear" => { self.breakpoints.clear(); Ok(()) }
+            "stack" => {
+                for val in self.vm.stack() { try!(writeln!(out, "  {}", val)) }
+                Ok(())
+            }
+            "env" => match arg.parse::<usize>().unwrap_or(0) {
+                n if n < self.vm.frames().len() => self.show_env(n, out),
+                n => writeln!(out, "no frame {}", n),
+            },
+            "frames" | "bt" => self.show_frames(out),
+            "print" | "p" => match self.eval(arg) {
+                Ok(val) => writeln!(out, "{}", val),
+                Err(e) => writeln!(out, "error: {}", e),
+            },
+            _ => writeln!(out, "unknown command `{}`", cmd),
+        }
+    }
+
+    // Steps until `stop' says to, a breakpoint is hit, or the program ends or
+    // fails.
+    fn resume<F: Fn(&VM) -> bool>(&mut self, stop: F) -> Stop {
+        loop {
+            if self.vm.done() { return Stop::Done }
+            if let Err(e) = self.vm.step() { return Stop::Failed(e) }
+            if self.vm.done() { return Stop::Done }
+            if stop(&self.vm) || self.at_breakpoint() { return Stop::Paused }
+        }
+    }
+
+    fn breakpoint(&self, arg: &str) -> Result<Breakpoint, String> {
+        if let Ok(line) = arg.parse::<usize>() {
+            return Ok(Breakpoint::Line(line))
+        }
+        if arg.is_empty() { return Err("break LINE, NAME or pN".into()) }
+        // Ids as the disassembler numbers protos, outermost first.
+        if arg.starts_with('p') {
+            if let Ok(id) = arg[1..].parse::<usize>() {
+                return match protos(&self.root).get(id) {
+                    Some(&p) => Ok(Breakpoint::Id(p)),
+                    None => Err(format!("no proto {}", arg)),
+                }
+            }
+        }
+        Ok(Breakpoint::Proto(Symbol::intern(arg)))
+    }
+
+    // Whether any call written on `line' could stop a line breakpoint.
+    fn has_calls(&self, line: usize) -> bool {
+        protos(&self.root).iter().filter_map(|p| p.debug.as_ref())
+            .any(|debug| debug.spans.iter()
+                 .any(|s| s.1.line_col(self.src).0 == line))
+    }
+
+    fn at_breakpoint(&self) -> bool {
+        if self.breakpoints.is_empty() { return false }
+        let (proto, ip) = self.vm.location();
+        let debug = proto.debug.as_ref();
+        self.breakpoints.iter().any(|bp| match *bp {
+            Breakpoint::Proto(ref name) =>
+                ip == 0 && debug.and_then(|d| d.name.as_ref()) == Some(name),
+            Breakpoint::Id(p) => ip == 0 && &**proto as *const Proto == p,
+            // Only calls have positions, so these stop just before a call.
+            Breakpoint::Line(line) => debug.and_then(|d| {
+                proto.instr_index(ip).and_then(|i| d.span_at(i))
+            }).map_or(false, |span| span.line_col(self.src).0 == line),
+        })
+    }
+
+    fn show_location<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let frames = self.vm.frames();
+        let frame = &frames[0];
+        let instr = frame.index.map_or(String::new(), |i| {
+            format!("  {}", show_instr(&frame.proto.instrs()[i]))
+        });
+        let trace = &self.vm.backtrace()[0];
+        write!(out, "{}{}\n", trace.render(self.src).lines().next().unwrap(),
+               instr)
+    }
+
+    fn show_frames<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        for (i, frame) in self.vm.backtrace().iter().enumerate() {
+            try!(write!(out, "#{}{}", i, frame.render(self.src)))
+        }
+        Ok(())
+    }
+
+    fn show_env<W: Write>(&self, n: usize, out: &mut W) -> io::Result<()> {
+        let frame = &self.vm.frames()[n];
+        let names = names(frame);
+        for (i, val) in frame.env.iter().enumerate() {
+            match names.get(i) {
+                Some(name) => try!(writeln!(out, "  {} = {}", name, val)),
+                None => try!(writeln!(out, "  ${} = {}", i, val)),
+            }
+        }
+        Ok(())
+    }
+
+    // Evaluates `src' in a fresh VM, with copies of the current frame's
+    // variables. Assigning to them doesn't change the program's.
+    fn eval(&self, src: &str) -> Result<Val, String> {
+        let frames = self.vm.frames();
+        let frame = &frames[0];
+        let names = names(frame);
+        // Without names for every variable, we can't refer to any.
+        let (names, env) = if names.len() == frame.env.len() {
+            (names, frame.env.iter().map(unbox).collect())
+        } else {
+            (vec![], vec![])
+        };
+        let s = try!(Sexp::from_str(src).map_err(|e| e.to_string()));
+        let e = try!(parse_in(&s, &names).map_err(|e| e.to_string()));
+        VM::new_in(compile_in(&e, &names), env)
+            .and_then(|mut vm| vm.finish().map(|()| vm.value()))
+            .map_err(|e| e.to_string())
+    }
+}
+
+// The names of a frame's variables, innermost last, if it has debug info.
+fn names(frame: &FrameView) -> Vec<Ident> {
+    match (frame.proto.debug.as_ref(), frame.index) {
+        (Some(debug), Some(index)) => debug.names_at(index),
+        _ => vec![],
+    }
+}
+
+fn unbox(val: &Val) -> Val {
+    match *val { Val::Ref(ref r) => r.borrow().clone(), ref v => v.clone() }
+}
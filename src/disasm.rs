@@ -0,0 +1,161 @@
+// Shows compiled code in a form people can read. Each proto is listed
+// separately, with an id that closures refer to it by:
+//
+//   (proto p0 (arity 0)
+//     0  (push 1)
+//     1  (if L2 L4)
+//   L2:
+//     2  (get 0)     ; x
+//     ...)
+//
+// Instructions are numbered, jump targets get labels, and where the compiler
+// left debug info, protos are shown with their names and parameters, and
+// variable indices with the variables' names.
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::rc::Rc;
+
+use cam::*;
+use lang::*;
+
+pub fn disassemble(proto: &Proto) -> String {
+    let d = Disassembler::new(proto);
+    let mut out = String::new();
+    for (i, p) in d.protos.iter().enumerate() {
+        if i > 0 { out.push('\n') }
+        d.proto(&mut out, i, p);
+    }
+    out
+}
+
+// The protos `proto' can reach, itself included, in the order their ids
+// number them.
+pub fn protos(proto: &Proto) -> Vec<&Proto> {
+    Disassembler::new(proto).protos
+}
+
+struct Disassembler<'a> {
+    // Protos are numbered in the order they're found, outermost first. The
+    // same proto can be reachable more than once, so we key on its address.
+    ids: HashMap<*const Proto, usize>,
+    protos: Vec<&'a Proto>,
+}
+
+impl<'a> Disassembler<'a> {
+    fn new(proto: &'a Proto) -> Disassembler<'a> {
+        let mut d = Disassembler { ids: HashMap::new(), protos: vec![] };
+        d.number(proto);
+        d
+    }
+
+    fn number(&mut self, proto: &'a Proto) {
+        if self.ids.contains_key(&(proto as *const Proto)) { return }
+        self.ids.insert(proto, self.protos.len());
+        self.protos.push(proto);
+        for p in &proto.protos { self.number(p) }
+    }
+
+    fn id(&self, proto: &Rc<Proto>) -> usize {
+        self.ids[&(&**proto as *const Proto)]
+    }
+
+    fn proto(&self, out: &mut String, id: usize, proto: &Proto) {
+        let code = proto.instrs();
+        let mut labels = vec![false; code.len() + 1];
+        for instr in &code {
+            match *instr {
+                Instr::Jump(t) => labels[t as usize] = true,
+                Instr::If(t, e) => { labels[t as usize] = true;
+                                     labels[e as usize] = true }
+                _ => {}
+            }
+        }
+
+        write!(out, "(proto p{} (arity {})", id, proto.arity).unwrap();
+        if let Some(ref debug) = proto.debug {
+            // The parameters are the innermost variables on entry.
+            let params = &debug.scope[debug.scope.len() - proto.arity as usize..];
+            let header = match (debug.name.as_ref(), params.is_empty()) {
+                (Some(name), true) => name.to_string(),
+                (Some(name), false) => format!("{}: {}", name, names(params)),
+                (None, _) => names(params),
+            };
+            if !header.is_empty() { write!(out, "  ; {}", header).unwrap() }
+        }
+        for (i, instr) in code.iter().enumerate() {
+            out.push('\n');
+            if labels[i] { write!(out, "L{}:\n", i).unwrap() }
+            let text = instr_text(instr, |p| format!("p{}", self.id(p)));
+            let comment = proto.debug.as_ref().and_then(|d| comment(d, i, instr));
+            match comment {
+                Some(c) => write!(out, "  {:>3}  {:<20}; {}", i, text, c),
+                None => write!(out, "  {:>3}  {}", i, text),
+            }.unwrap();
+        }
+        out.push_str(")\n");
+    }
+}
+
+// A single instruction, out of context. Closures show their proto's name.
+pub fn show_instr(instr: &Instr) -> String {
+    instr_text(instr, |p| {
+        p.debug.as_ref().and_then(|d| d.name.as_ref())
+            .map_or("<fn>".to_string(), |n| n.to_string())
+    })
+}
+
+fn instr_text<F>(instr: &Instr, proto: F) -> String
+    where F: Fn(&Rc<Proto>) -> String
+{
+    use cam::Instr::*;
+    match *instr {
+        Get(i) => format!("(get {})", i),
+        Set(i) => format!("(set {})", i),
+        BoxVar(i) => format!("(box {})", i),
+        GetBox(i) => format!("(get-box {})", i),
+        SetBox(i) => format!("(set-box {})", i),
+        Push(ref l) => format!("(push {})", lit(l)),
+        Pop => "(pop)".to_string(),
+        Bind(n) => format!("(bind {})", n),
+        Unbind(n) => format!("(unbind {})", n),
+        Apply(n) => format!("(apply {})", n),
+        TailApply(n) => format!("(tail-apply {})", n),
+        Prim(p) => format!("(prim {})", p),
+        Closure(ref p) => format!("(closure {})", proto(p)),
+        If(t, e) => format!("(if L{} L{})", t, e),
+        Jump(t) => format!("(jump L{})", t),
+        Return => "(return)".to_string(),
+    }
+}
+
+// Literals are written so they read back as themselves.
+fn lit(l: &Lit) -> String {
+    match *l {
+        Lit::Symbol(ref s) => format!("'{}", s),
+        ref l => format!("{}", l),
+    }
+}
+
+fn names(ids: &[Ident]) -> String {
+    let names: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+    names.join(" ")
+}
+
+// The names of the variables an instruction refers to, if we know them.
+fn comment(debug: &DebugInfo, index: usize, instr: &Instr) -> Option<String> {
+    use cam::Instr::*;
+    let var = match *instr {
+        Get(i) | Set(i) | BoxVar(i) | GetBox(i) | SetBox(i) => i as usize,
+        // Bind's variables come into scope just after it.
+        Bind(n) => {
+            let scope = debug.names_at(index + 1);
+            let n = n as usize;
+            if n > scope.len() { return None }
+            return Some(names(&scope[scope.len() - n..]))
+        }
+        _ => return None,
+    };
+    let scope = debug.names_at(index);
+    if var >= scope.len() { return None }
+    Some(scope[scope.len() - 1 - var].to_string())
+}
@@ -0,0 +1,299 @@
+// Saving compiled code to files and loading it back.
+//
+// A file is the magic bytes "CAMB", a 16-bit format version, a 32-bit FNV-1a
+// checksum of the rest of the file, the names of the prims, and then the
+// top-level proto. All fixed width integers are little-endian; counts and
+// lengths are LEB128 varints.
+//
+//   prims := prim-count (name-length name)*
+//   proto := arity code-length code literal-count literal*
+//            proto-count proto*
+//   literal := tag data, where the tag is one of the LIT_ constants below.
+//
+// Numbers too big for a fixnum are written in decimal. Prim instructions
+// refer to prims by their index in the file's list, and prim literals by
+// name, so that reordering the prims doesn't invalidate files.
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::io::{Read,Write};
+use std::rc::Rc;
+
+use bigint::BigInt;
+use bytecode::*;
+use cam::Proto;
+use lang::*;
+use num;
+use ratio::Ratio;
+use string::{Str,Symbol};
+use verify::{verify,VerifyError};
+
+const MAGIC: &'static [u8] = b"CAMB";
+pub const VERSION: u16 = 2;
+const HEADER_LEN: usize = 10;
+// Protos nested deeper than this are rejected rather than risk overflowing
+// the stack.
+const MAX_NESTING: usize = 256;
+
+const LIT_NIL: u8 = 0;
+const LIT_FALSE: u8 = 1;
+const LIT_TRUE: u8 = 2;
+const LIT_INT: u8 = 3;
+const LIT_BIG: u8 = 4;
+const LIT_RATIO: u8 = 5;
+const LIT_FLOAT: u8 = 6;
+const LIT_CHAR: u8 = 7;
+const LIT_STRING: u8 = 8;
+const LIT_PRIM: u8 = 9;
+const LIT_SYMBOL: u8 = 10;
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    // The file doesn't start with the magic bytes.
+    NotBytecode,
+    // The file is in a version of the format we don't read.
+    Version(u16),
+    Checksum,
+    Truncated,
+    Malformed(&'static str),
+    // The code decodes, but uses the stack wrongly.
+    Verify(VerifyError),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(),fmt::Error> {
+        match *self {
+            LoadError::Io(ref e) => e.fmt(f),
+            LoadError::NotBytecode => f.write_str("not a bytecode file"),
+            LoadError::Version(v) =>
+                write!(f, "bytecode version {} is not supported (expected {})",
+                       v, VERSION),
+            LoadError::Checksum => f.write_str("checksum mismatch"),
+            LoadError::Truncated => f.write_str("file is truncated"),
+            LoadError::Malformed(what) => write!(f, "malformed bytecode: {}", what),
+            LoadError::Verify(ref e) => write!(f, "invalid bytecode: {}", e),
+        }
+    }
+}
+
+impl Error for LoadError {}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> LoadError { LoadError::Io(e) }
+}
+
+pub type LoadResult<A> = Result<A, LoadError>;
+
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c9dc5, |h, &b| (h ^ b as u32).wrapping_mul(0x01000193))
+}
+
+// ---------- Saving ----------
+pub fn save<W: Write>(proto: &Proto, w: &mut W) -> io::Result<()> {
+    w.write_all(&to_bytes(proto))
+}
+
+pub fn to_bytes(proto: &Proto) -> Vec<u8> {
+    let mut body = vec![];
+    // Code numbers prims as Prim::from_index does.
+    let mut prims = vec![];
+    while let Some(p) = Prim::from_index(prims.len() as u8) { prims.push(p) }
+    write_varint(&mut body, prims.len() as u32);
+    for p in prims { write_bytes(&mut body, p.name().as_bytes()) }
+    write_proto(&mut body, proto);
+    let mut out = Vec::with_capacity(HEADER_LEN + body.len());
+    out.extend(MAGIC);
+    write_u16(&mut out, VERSION);
+    let sum = checksum(&body);
+    for i in 0..4 { out.push((sum >> (8 * i)) as u8) }
+    out.extend(body);
+    out
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_varint(out, bytes.len() as u32);
+    out.extend(bytes);
+}
+
+fn write_proto(out: &mut Vec<u8>, proto: &Proto) {
+    write_varint(out, proto.arity);
+    write_bytes(out, &proto.code);
+    write_varint(out, proto.consts.len() as u32);
+    for l in &proto.consts { write_lit(out, l) }
+    write_varint(out, proto.protos.len() as u32);
+    for p in &proto.protos { write_proto(out, p) }
+}
+
+fn write_lit(out: &mut Vec<u8>, l: &Lit) {
+    match *l {
+        Lit::Nil => out.push(LIT_NIL),
+        Lit::Bool(b) => out.push(if b { LIT_TRUE } else { LIT_FALSE }),
+        Lit::Int(x) => {
+            out.push(LIT_INT);
+            for i in 0..8 { out.push((x >> (8 * i)) as u8) }
+        }
+        Lit::Big(ref b) => {
+            out.push(LIT_BIG);
+            write_bytes(out, b.to_string().as_bytes());
+        }
+        Lit::Ratio(ref r) => {
+            out.push(LIT_RATIO);
+            write_bytes(out, r.numer().to_string().as_bytes());
+            write_bytes(out, r.denom().to_string().as_bytes());
+        }
+        Lit::Float(x) => {
+            out.push(LIT_FLOAT);
+            let bits = x.to_bits();
+            for i in 0..8 { out.push((bits >> (8 * i)) as u8) }
+        }
+        Lit::Char(c) => {
+            out.push(LIT_CHAR);
+            write_varint(out, c as u32);
+        }
+        Lit::String(ref s) => { out.push(LIT_STRING); write_bytes(out, s.as_bytes()) }
+        Lit::Prim(p) => { out.push(LIT_PRIM); write_bytes(out, p.name().as_bytes()) }
+        Lit::Symbol(ref s) => { out.push(LIT_SYMBOL); write_bytes(out, s.as_bytes()) }
+    }
+}
+
+// ---------- Loading ----------
+pub fn load<R: Read>(r: &mut R) -> LoadResult<Proto> {
+    let mut bytes = vec![];
+    try!(r.read_to_end(&mut bytes));
+    from_bytes(&bytes)
+}
+
+// Checks everything about the code that could make the VM misbehave before
+// it starts running: that it decodes, and that it keeps the stack in order.
+pub fn from_bytes(bytes: &[u8]) -> LoadResult<Proto> {
+    if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(if MAGIC.starts_with(bytes) { LoadError::Truncated }
+                   else { LoadError::NotBytecode })
+    }
+    if bytes.len() < HEADER_LEN { return Err(LoadError::Truncated) }
+    let version = bytes[4] as u16 | (bytes[5] as u16) << 8;
+    if version != VERSION { return Err(LoadError::Version(version)) }
+    let sum = (0..4).fold(0, |s, i| s | (bytes[6 + i] as u32) << (8 * i));
+    let body = &bytes[HEADER_LEN..];
+    if checksum(body) != sum { return Err(LoadError::Checksum) }
+
+    let mut r = Reader { bytes: body, pos: 0, prims: vec![] };
+    for _ in 0..try!(r.count()) {
+        let prim = Prim::from_name(try!(r.str()));
+        r.prims.push(prim)
+    }
+    let proto = try!(r.proto(0));
+    if r.pos != body.len() { return Err(LoadError::Malformed("trailing bytes")) }
+    if proto.arity != 0 {
+        return Err(LoadError::Malformed("top-level code takes arguments"))
+    }
+    try!(proto.check().map_err(LoadError::Malformed));
+    try!(verify(&proto).map_err(LoadError::Verify));
+    Ok(proto)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    // The file's prims, by the index its code uses; those we don't have are
+    // only an error if they're used.
+    prims: Vec<Option<Prim>>,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> LoadResult<&'a [u8]> {
+        if n > self.bytes.len() - self.pos { return Err(LoadError::Truncated) }
+        let bytes = &self.bytes[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> LoadResult<u8> { self.take(1).map(|b| b[0]) }
+
+    fn u64(&mut self) -> LoadResult<u64> {
+        let b = try!(self.take(8));
+        Ok((0..8).fold(0, |x, i| x | (b[i] as u64) << (8 * i)))
+    }
+
+    fn varint(&mut self) -> LoadResult<u32> {
+        let start = self.pos;
+        match try_read_varint(self.bytes, &mut self.pos) {
+            Some(x) => Ok(x),
+            None if self.pos >= self.bytes.len() => Err(LoadError::Truncated),
+            None => { self.pos = start; Err(LoadError::Malformed("bad varint")) }
+        }
+    }
+
+    // A count of things each at least a byte long, so it can't exceed what's
+    // left; this stops a corrupt count from making us allocate wildly.
+    fn count(&mut self) -> LoadResult<usize> {
+        let n = try!(self.varint()) as usize;
+        if n > self.bytes.len() - self.pos { return Err(LoadError::Truncated) }
+        Ok(n)
+    }
+
+    fn bytes(&mut self) -> LoadResult<&'a [u8]> {
+        let n = try!(self.varint()) as usize;
+        self.take(n)
+    }
+
+    fn str(&mut self) -> LoadResult<&'a str> {
+        let bytes = try!(self.bytes());
+        ::std::str::from_utf8(bytes).map_err(|_| LoadError::Malformed("bad UTF-8"))
+    }
+
+    fn big(&mut self) -> LoadResult<BigInt> {
+        try!(self.str()).parse().map_err(|_| LoadError::Malformed("bad integer"))
+    }
+
+    fn proto(&mut self, depth: usize) -> LoadResult<Proto> {
+        if depth > MAX_NESTING {
+            return Err(LoadError::Malformed("protos nested too deeply"))
+        }
+        let arity = try!(self.varint());
+        let code = try!(self.bytes()).to_vec();
+        let mut consts = vec![];
+        for _ in 0..try!(self.count()) { consts.push(try!(self.lit())) }
+        let mut protos = vec![];
+        for _ in 0..try!(self.count()) {
+            protos.push(Rc::new(try!(self.proto(depth + 1))))
+        }
+        let mut proto = Proto { arity: arity, code: code, consts: consts,
+                                protos: protos, debug: None };
+        let prims = &self.prims;
+        try!(proto.map_prims(|i| prims.get(i as usize).and_then(|&p| p))
+             .map_err(LoadError::Malformed));
+        Ok(proto)
+    }
+
+    fn lit(&mut self) -> LoadResult<Lit> {
+        Ok(match try!(self.byte()) {
+            LIT_NIL => Lit::Nil,
+            LIT_FALSE => Lit::Bool(false),
+            LIT_TRUE => Lit::Bool(true),
+            LIT_INT => Lit::Int(try!(self.u64()) as i64),
+            LIT_BIG => num::from_big(try!(self.big())),
+            LIT_RATIO => {
+                let n = try!(self.big());
+                let d = try!(self.big());
+                if d.is_zero() {
+                    return Err(LoadError::Malformed("zero denominator"))
+                }
+                num::from_ratio(Ratio::new(n, d))
+            }
+            LIT_FLOAT => Lit::Float(f64::from_bits(try!(self.u64()))),
+            LIT_CHAR => match ::std::char::from_u32(try!(self.varint())) {
+                Some(c) => Lit::Char(c),
+                None => return Err(LoadError::Malformed("bad character")),
+            },
+            LIT_STRING => Lit::String(Str::new(try!(self.str()))),
+            LIT_PRIM => match Prim::from_name(try!(self.str())) {
+                Some(p) => Lit::Prim(p),
+                None => return Err(LoadError::Malformed("unknown prim")),
+            },
+            LIT_SYMBOL => Lit::Symbol(Symbol::intern(try!(self.str()))),
+            _ => return Err(LoadError::Malformed("unknown literal tag")),
+        })
+    }
+}
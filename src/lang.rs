@@ -1,49 +1,134 @@
-use std::collections::HashMap;
+use std::error::Error;
 use std::fmt;
+use std::cmp::Ordering;
+use std::rc::Rc;
 
-use string::Str;
-use sexp::Sexp;
+use bigint::BigInt;
+use ratio::Ratio;
+use string::{Str,Symbol};
+use sexp;
+use sexp::{Sexp,SexpKind,Span};
 use parse::{ParseFrom};
+use num;
 
 pub type VarIndex = u32;
 // TODO?: use usize for arity everywhere except in representation of bytecode.
 // otherwise it just gets annoying.
 pub type Arity = u32;
-pub type Ident = Str;
+pub type Ident = Symbol;
 
-#[derive(Clone,Copy,PartialEq,Eq,PartialOrd,Ord,Debug)]
-pub enum Prim {
-    Equal, Leq, Add, Sub, Mul, Div, Print,
-}
-pub use self::Prim::*;
-impl Prim {
-    pub fn arity(&self) -> Arity {
-        match *self {
-            Equal | Leq | Add | Sub | Mul | Div => 2,
-            Print => 1,
+// Defines the Prim enum along with each prim's name and arity.
+macro_rules! prims {
+    ($($prim:ident $name:literal $arity:expr;)*) => {
+        #[derive(Clone,Copy,PartialEq,Eq,PartialOrd,Ord,Debug)]
+        pub enum Prim { $($prim),* }
+        impl Prim {
+            pub fn arity(&self) -> Arity {
+                match *self { $(Prim::$prim => $arity),* }
+            }
+            pub fn name(&self) -> &'static str {
+                match *self { $(Prim::$prim => $name),* }
+            }
+            pub fn from_name(name: &str) -> Option<Prim> {
+                match name { $($name => Some(Prim::$prim),)* _ => None }
+            }
+            // Inverse to `prim as u8'.
+            pub fn from_index(i: u8) -> Option<Prim> {
+                const ALL: &'static [Prim] = &[$(Prim::$prim),*];
+                ALL.get(i as usize).cloned()
+            }
         }
     }
 }
 
-#[derive(Clone,PartialEq,Eq,PartialOrd,Ord,Debug)]
-pub enum Lit { Nil, Bool(bool), Int(i64), String(Str), Prim(Prim) }
+prims! {
+    Equal "eq" 2; Leq "le" 2;
+    Add "add" 2; Sub "sub" 2; Mul "mul" 2; Div "div" 2;
+    Floor "floor" 1; Ceiling "ceiling" 1; Round "round" 1;
+    Truncate "truncate" 1; Sqrt "sqrt" 1;
+    ExactToInexact "exact->inexact" 1; InexactToExact "inexact->exact" 1;
+    Numerator "numerator" 1; Denominator "denominator" 1;
+    Cons "cons" 2; Car "car" 1; Cdr "cdr" 1;
+    IsNull "null?" 1; IsPair "pair?" 1;
+    StringAppend "string-append" 2; StringLength "string-length" 1;
+    Substring "substring" 3; StringIndex "string-index" 2;
+    StringToNumber "string->number" 1; NumberToString "number->string" 1;
+    StringUpcase "string-upcase" 1;
+    CharToInteger "char->integer" 1; IntegerToChar "integer->char" 1;
+    StringRef "string-ref" 2; StringToList "string->list" 1;
+    IsSymbol "symbol?" 1;
+    SymbolToString "symbol->string" 1; StringToSymbol "string->symbol" 1;
+    StringSplit "string-split" 2; StringJoin "string-join" 2;
+    Print "print" 1;
+}
+pub use self::Prim::*;
+
+#[derive(Clone,Debug)]
+pub enum Lit {
+    Nil, Bool(bool), Int(i64), Big(Rc<BigInt>), Ratio(Rc<Ratio>), Float(f64),
+    Char(char), String(Str), Prim(Prim),
+    Symbol(Symbol),
+}
 impl Lit {
     pub fn truthy(&self) -> bool {
         match *self { Lit::Nil | Lit::Bool(false) => false,
                       _ => true }
     }
+    fn rank(&self) -> u8 {
+        match *self { Lit::Nil => 0, Lit::Bool(_) => 1,
+                      Lit::Int(_) | Lit::Big(_) | Lit::Ratio(_) => 2,
+                      Lit::Float(_) => 3, Lit::Char(_) => 4,
+                      Lit::String(_) => 5, Lit::Prim(_) => 6,
+                      Lit::Symbol(_) => 7 }
+    }
+}
+
+// Lit's equality and ordering are structural: Int(1) and Float(1.0) are
+// different literals, and floats are totally ordered as by IEEE 754
+// totalOrder, so -0.0 < 0.0 and NaN equals itself. Numeric comparison, where
+// 1 = 1.0 and NaN is incomparable, lives in the num module.
+impl PartialEq for Lit {
+    fn eq(&self, other: &Lit) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl Eq for Lit {}
+impl PartialOrd for Lit {
+    fn partial_cmp(&self, other: &Lit) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Lit {
+    fn cmp(&self, other: &Lit) -> Ordering {
+        match (self, other) {
+            (&Lit::Bool(a), &Lit::Bool(b)) => a.cmp(&b),
+            (&Lit::Int(a), &Lit::Int(b)) => a.cmp(&b),
+            // Exact numbers have canonical representations, so we can order
+            // them by value.
+            _ if num::is_exact(self) && num::is_exact(other) =>
+                num::cmp_exact(self, other),
+            (&Lit::Float(a), &Lit::Float(b)) => a.total_cmp(&b),
+            (&Lit::Char(a), &Lit::Char(b)) => a.cmp(&b),
+            (&Lit::String(ref a), &Lit::String(ref b)) => a.cmp(b),
+            (&Lit::Prim(a), &Lit::Prim(b)) => a.cmp(&b),
+            (&Lit::Symbol(ref a), &Lit::Symbol(ref b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
 }
 
 pub type Expr = Box<Exp>;
-#[derive(Debug)]
+#[derive(Clone,Debug)]
 pub enum Exp {
     Lit(Lit),
     Var(Ident, VarIndex),
     Lam(Vec<Ident>, Expr),
-    App(Expr, Vec<Exp>),
+    // Where the application was written, for error messages.
+    App(Expr, Vec<Exp>, Span),
     If(Expr, Expr, Expr),
     // simultaneous binding; no let-bound expression sees any of the others.
     Let(Vec<(Ident,Exp)>, Expr),
+    Set(Ident, VarIndex, Expr),
 }
 
 // ---------- Displaying exps. ----------
@@ -58,7 +143,7 @@ impl fmt::Display for Exp {
                 for i in &ids[1..] { try!(write!(f, ",{}", i)) }
                 write!(f, " -> {}", body)
             }
-            Exp::App(ref e, ref args) => {
+            Exp::App(ref e, ref args, _) => {
                 if args.is_empty() { return write!(f, "{}()", e) }
                 try!(write!(f, "{}({}", e, args[0]));
                 for a in &args[1..] { try!(write!(f, ", {}", a)) }
@@ -74,6 +159,7 @@ impl fmt::Display for Exp {
                 }
                 write!(f, " in {}", body)
             }
+            Exp::Set(ref name, _, ref e) => write!(f, "{} := {}", name, e),
         }
     }
 }
@@ -84,142 +170,302 @@ impl fmt::Display for Lit {
             Lit::Nil => f.write_str("nil"),
             Lit::Bool(b) => b.fmt(f),
             Lit::Int(i) => i.fmt(f),
+            Lit::Big(ref b) => b.fmt(f),
+            Lit::Ratio(ref r) => r.fmt(f),
+            Lit::Float(x) => num::fmt_float(x, f),
+            Lit::Char(c) => sexp::fmt_char(c, f),
             Lit::String(ref s) => write!(f, "{:?}", s as &str),
             Lit::Prim(ref p) => p.fmt(f),
+            Lit::Symbol(ref s) => s.fmt(f),
         }
     }
 }
 
 impl fmt::Display for Prim {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(),fmt::Error> {
-        f.write_str(match *self {
-            Equal => "eq", Leq => "le",
-            Add => "add", Sub => "sub", Mul => "mul", Div => "div",
-            Print => "print",
-        })
+        f.write_str(self.name())
+    }
+}
+
+// ---------- Syntax errors. ----------
+#[derive(Clone,PartialEq,Debug)]
+pub enum SyntaxError {
+    UnboundVariable(Ident, Span),
+    BadArity { form: &'static str, expected: usize, got: usize, span: Span },
+    // fn's parameters weren't a list of symbols.
+    MalformedParams(Span),
+    // let's bindings weren't a list of (name expression) pairs.
+    MalformedBindings(Span),
+    EmptyApplication(Span),
+    ExpectedSymbol(Span),
+    UnknownPrim(Ident, Span),
+    NotALiteral(Span),
+    // Errors in assembly code; see asm.rs.
+    UnknownInstr(Ident, Span),
+    // An operand wasn't a number in range, label or proto as needed.
+    BadOperand(Span),
+    UnknownLabel(Ident, Span),
+    DuplicateLabel(Ident, Span),
+    UnknownProto(Ident, Span),
+    DuplicateProto(Ident, Span),
+    RecursiveProto(Ident, Span),
+    // Wasn't (proto [id] (arity n) ...).
+    MalformedProto(Span),
+}
+
+impl SyntaxError {
+    pub fn span(&self) -> Span {
+        match *self {
+            SyntaxError::UnboundVariable(_, span) => span,
+            SyntaxError::BadArity { span, .. } => span,
+            SyntaxError::MalformedParams(span) => span,
+            SyntaxError::MalformedBindings(span) => span,
+            SyntaxError::EmptyApplication(span) => span,
+            SyntaxError::ExpectedSymbol(span) => span,
+            SyntaxError::UnknownPrim(_, span) => span,
+            SyntaxError::NotALiteral(span) => span,
+            SyntaxError::UnknownInstr(_, span) => span,
+            SyntaxError::BadOperand(span) => span,
+            SyntaxError::UnknownLabel(_, span) => span,
+            SyntaxError::DuplicateLabel(_, span) => span,
+            SyntaxError::UnknownProto(_, span) => span,
+            SyntaxError::DuplicateProto(_, span) => span,
+            SyntaxError::RecursiveProto(_, span) => span,
+            SyntaxError::MalformedProto(span) => span,
+        }
+    }
+
+    // The error message along with the offending part of `src', which should
+    // be the text the sexp was read from.
+    pub fn render(&self, src: &str) -> String {
+        self.span().render(src, &self.to_string())
+    }
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(),fmt::Error> {
+        match *self {
+            SyntaxError::UnboundVariable(ref name, _) =>
+                write!(f, "unbound variable `{}`", name),
+            SyntaxError::BadArity { form, expected, got, .. } =>
+                write!(f, "{} takes {} argument{}, got {}", form, expected,
+                       if expected == 1 { "" } else { "s" }, got),
+            SyntaxError::MalformedParams(_) =>
+                f.write_str("fn parameters must be a list of symbols"),
+            SyntaxError::MalformedBindings(_) =>
+                f.write_str("let bindings must be a list of (name expression) pairs"),
+            SyntaxError::EmptyApplication(_) =>
+                f.write_str("empty list is not an expression"),
+            SyntaxError::ExpectedSymbol(_) => f.write_str("expected a symbol"),
+            SyntaxError::UnknownPrim(ref name, _) =>
+                write!(f, "unrecognized prim `{}`", name),
+            SyntaxError::NotALiteral(_) => f.write_str("invalid literal"),
+            SyntaxError::UnknownInstr(ref name, _) =>
+                write!(f, "unrecognized instruction `{}`", name),
+            SyntaxError::BadOperand(_) => f.write_str("invalid operand"),
+            SyntaxError::UnknownLabel(ref name, _) =>
+                write!(f, "undefined label `{}`", name),
+            SyntaxError::DuplicateLabel(ref name, _) =>
+                write!(f, "label `{}` is defined twice", name),
+            SyntaxError::UnknownProto(ref name, _) =>
+                write!(f, "undefined proto `{}`", name),
+            SyntaxError::DuplicateProto(ref name, _) =>
+                write!(f, "proto `{}` is defined twice", name),
+            SyntaxError::RecursiveProto(ref name, _) =>
+                write!(f, "proto `{}` contains itself", name),
+            SyntaxError::MalformedProto(_) =>
+                f.write_str("expected (proto [id] (arity n) instruction...)"),
+        }
     }
 }
 
+impl Error for SyntaxError {}
+
 // ---------- Parsing sexps into exps. ----------
-type ParseEnv = HashMap<Str,usize>;
-type ParseResult<A> = Result<A,String>;
-fn err<A,S>(s: S) -> ParseResult<A> where String: From<S> {
-    Err(String::from(s))
+// The variables in scope, innermost last. A variable's DeBruijn index is its
+// distance from the end.
+type ParseEnv = Vec<Ident>;
+type ParseResult<A> = Result<A,SyntaxError>;
+
+fn arity<A>(form: &'static str, expected: usize, s: &Sexp, args: &[Sexp])
+            -> ParseResult<A>
+{
+    Err(SyntaxError::BadArity { form: form, expected: expected,
+                                got: args.len(), span: s.span })
+}
+
+fn lookup(env: &ParseEnv, name: &Symbol) -> Option<VarIndex> {
+    env.iter().rev().position(|x| x == name).map(|i| i as VarIndex)
 }
 
 fn parse(s: &Sexp, env: &mut ParseEnv) -> ParseResult<Exp> {
-    Lit::parse_from(s).map(Exp::Lit).or_else(|_| {
+    // Local variables shadow literals & prims.
+    if let SexpKind::Symbol(ref s) = s.kind {
+        if let Some(idx) = lookup(env, s) {
+            return Ok(Exp::Var(s.clone(), idx))
+        }
+    }
+    Lit::parse_from(s).map(Exp::Lit).or_else(|e| {
         // but otherwise...
-        match *s {
-            Sexp::List(ref exps) if exps.is_empty()
-                => err("empty list is not an expression"),
-            Sexp::List(ref exps) => match exps[0] {
-                Sexp::Symbol(ref s) => parse_form(env, &**s, exps),
+        match s.kind {
+            SexpKind::List(ref exps) if exps.is_empty()
+                => Err(SyntaxError::EmptyApplication(s.span)),
+            SexpKind::List(ref exps) => match exps[0].kind {
+                SexpKind::Symbol(ref name) => parse_form(env, &**name, s, exps),
                 // List beginning with non-symbol is always application
-                _ => parse_app(env, exps)
+                _ => parse_app(env, exps, s.span)
             },
-            Sexp::Symbol(ref s) => match env.get(s) {
-                Some(idx) => Ok(Exp::Var(s.clone(), *idx as VarIndex)),
-                None => err("unbound variable")
-            },
-            _ => err("cannot parse expression")
+            SexpKind::Symbol(ref name) =>
+                Err(SyntaxError::UnboundVariable(name.clone(), s.span)),
+            _ => Err(e)
         }
     })
 }
 
-fn parse_form(env: &mut ParseEnv, form: &str, exps: &[Sexp]) -> ParseResult<Exp>
+// Parses `body' with `ids' bound, in order, on top of `env'.
+fn parse_scoped(env: &mut ParseEnv, ids: &[Ident], body: &Sexp)
+                -> ParseResult<Exp>
+{
+    let len = env.len();
+    env.extend(ids.iter().cloned());
+    let r = parse(body, env);
+    env.truncate(len);
+    r
+}
+
+// `s' is the whole form, and `exps' its elements.
+fn parse_form(env: &mut ParseEnv, form: &str, s: &Sexp, exps: &[Sexp])
+              -> ParseResult<Exp>
 {
     let args = &exps[1..];
     match form {
-        "var" if args.len() != 1 => err("var takes 1 argument"),
+        "var" if args.len() != 1 => arity("var", 1, s, args),
         "var" => unimplemented!(), // TODO: var expressions
-        "fn" if args.len() != 2 => err("fn takes 2 arguments"),
+        "fn" if args.len() != 2 => arity("fn", 2, s, args),
         "fn"  => {
-            let params = match args[0] {
-                Sexp::List(ref v) => v,
-                _ => return err("fn takes a list of parameters"),
+            let params = match args[0].kind {
+                SexpKind::List(ref v) => v,
+                _ => return Err(SyntaxError::MalformedParams(args[0].span)),
             };
-            // The .rev() is necessary to maintain variable-binding order for
-            // DeBruijn indices. Otherwise multi-argument functions take their
-            // arguments in reverse of the expected order.
-            params.iter().rev().map(|e| match *e {
-                Sexp::Symbol(ref n) => Ok(n.clone()),
+            params.iter().map(|e| match e.kind {
+                SexpKind::Symbol(ref n) => Ok(n.clone()),
                 // TODO?: allow strings?
-                _ => err("fn parameters must be symbols")
+                _ => Err(SyntaxError::MalformedParams(e.span))
             }).collect::<Result<Vec<_>,_>>().and_then(|ids| {
-                let len = env.len();
-                env.extend(ids.iter().enumerate()
-                           .map(|e| (e.1.clone(), len + e.0)));
-                let ids_new = ids.clone();
-                let r = parse(&args[1], env)
-                    .map(|body| Exp::Lam(ids_new, Box::new(body)));
-                for x in ids { env.remove(&*x); }
-                r
+                parse_scoped(env, &ids, &args[1])
+                    .map(|body| Exp::Lam(ids, Box::new(body)))
             })
         }
-        "app" => parse_app(env, args),
-        "if" if args.len() != 3 => err("if takes three arguments"),
+        "app" if args.is_empty() => Err(SyntaxError::EmptyApplication(s.span)),
+        "app" => parse_app(env, args, s.span),
+        "quote" if args.len() != 1 => arity("quote", 1, s, args),
+        "quote" => quote(&args[0]),
+        "if" if args.len() != 3 => arity("if", 3, s, args),
         // TODO: make a macro for this shit. maybe just use "try!"?
         "if"  => parse(&args[0], env).and_then(|cnd| {
             parse(&args[1], env).and_then(|thn| {
                 parse(&args[2], env).map(|els| {
                     Exp::If(Box::new(cnd), Box::new(thn), Box::new(els))})})}),
-        "let" if args.len() != 2 => err("let takes 2 arguments"),
+        "let" if args.len() != 2 => arity("let", 2, s, args),
         "let" => {
-            let ref _binds = args[0];
-            let ref _body = args[1];
-            unimplemented!()    // TODO: let expressions
+            let binds = match args[0].kind {
+                SexpKind::List(ref v) => v,
+                _ => return Err(SyntaxError::MalformedBindings(args[0].span)),
+            };
+            // The bound expressions are parsed in the outer scope.
+            binds.iter().map(|b| match b.kind {
+                SexpKind::List(ref v) if v.len() == 2 => match v[0].kind {
+                    SexpKind::Symbol(ref n) =>
+                        parse(&v[1], env).map(|e| (n.clone(), e)),
+                    _ => Err(SyntaxError::ExpectedSymbol(v[0].span)),
+                },
+                _ => Err(SyntaxError::MalformedBindings(b.span)),
+            }).collect::<Result<Vec<_>,_>>().and_then(|binds| {
+                let ids: Vec<Ident> = binds.iter().map(|b| b.0.clone())
+                                           .collect();
+                parse_scoped(env, &ids, &args[1])
+                    .map(|body| Exp::Let(binds, Box::new(body)))
+            })
         }
+        "set!" if args.len() != 2 => arity("set!", 2, s, args),
+        "set!" => match args[0].kind {
+            SexpKind::Symbol(ref n) => match lookup(env, n) {
+                Some(idx) => parse(&args[1], env).map(|e| {
+                    Exp::Set(n.clone(), idx, Box::new(e))}),
+                None => Err(SyntaxError::UnboundVariable(n.clone(),
+                                                         args[0].span)),
+            },
+            _ => Err(SyntaxError::ExpectedSymbol(args[0].span)),
+        },
         // otherwise, function application
-        _ => parse_app(env, exps)
+        _ => parse_app(env, exps, s.span)
     }
 }
 
-fn parse_app(env: &mut ParseEnv, exps: &[Sexp]) -> ParseResult<Exp> {
+// Quoted lists are built at runtime with cons.
+fn quote(s: &Sexp) -> ParseResult<Exp> {
+    match s.kind {
+        SexpKind::Symbol(ref s) => Ok(Exp::Lit(Lit::Symbol(s.clone()))),
+        SexpKind::List(ref v) =>
+            v.iter().rev().fold(Ok(Exp::Lit(Lit::Nil)), |l, e| {
+                l.and_then(|l| quote(e).map(|e| {
+                    Exp::App(Box::new(Exp::Lit(Lit::Prim(Cons))), vec![e, l],
+                             s.span)}))
+            }),
+        _ => Lit::parse_from(s).map(Exp::Lit),
+    }
+}
+
+fn parse_app(env: &mut ParseEnv, exps: &[Sexp], span: Span)
+             -> ParseResult<Exp>
+{
     parse(&exps[0], env).and_then(|func| {
         exps[1..].iter().map(|s| parse(s, env))
             .collect::<Result<Vec<_>,_>>()
-            .map(|args| Exp::App(Box::new(func), args))
+            .map(|args| Exp::App(Box::new(func), args, span))
     })
 }
 
 impl<'a> ParseFrom<&'a Sexp> for Exp {
-    type Error = String;
-    fn parse_from(s: &Sexp) -> Result<Exp,String> {
-        let mut env = HashMap::new();
-        parse(s, &mut env)
+    type Error = SyntaxError;
+    fn parse_from(s: &Sexp) -> ParseResult<Exp> {
+        parse_in(s, &[])
     }
 }
 
+// Parses `s' with the variables `ids' in scope, innermost last.
+pub fn parse_in(s: &Sexp, ids: &[Ident]) -> ParseResult<Exp> {
+    let mut env = ids.to_vec();
+    parse(s, &mut env)
+}
+
 impl<'a> ParseFrom<&'a Sexp> for Lit {
-    type Error = String;
+    type Error = SyntaxError;
     fn parse_from(s: &Sexp) -> ParseResult<Lit> {
-        match *s {
-            Sexp::Symbol(ref s) if s == "nil" => Ok(Lit::Nil),
-            Sexp::Symbol(ref s) if s == "true" => Ok(Lit::Bool(true)),
-            Sexp::Symbol(ref s) if s == "false" => Ok(Lit::Bool(false)),
-            Sexp::Int(n) => Ok(Lit::Int(n)),
-            Sexp::String(ref s) => Ok(Lit::String(s.clone())),
-            // TODO: better error message on failure
-            Sexp::Symbol(..) => Prim::parse_from(s).map(Lit::Prim),
-            _ => err("invalid literal")
+        match s.kind {
+            SexpKind::Symbol(ref s) if s == "nil" => Ok(Lit::Nil),
+            SexpKind::Symbol(ref s) if s == "true" => Ok(Lit::Bool(true)),
+            SexpKind::Symbol(ref s) if s == "false" => Ok(Lit::Bool(false)),
+            SexpKind::Int(n) => Ok(Lit::Int(n)),
+            SexpKind::Big(ref b) => Ok(Lit::Big(Rc::new(b.clone()))),
+            SexpKind::Ratio(ref n, ref d) =>
+                Ok(num::from_ratio(Ratio::new(n.clone(), d.clone()))),
+            SexpKind::Float(x) => Ok(Lit::Float(x)),
+            SexpKind::Char(c) => Ok(Lit::Char(c)),
+            SexpKind::String(ref s) => Ok(Lit::String(s.clone())),
+            SexpKind::Symbol(..) => Prim::parse_from(s).map(Lit::Prim),
+            _ => Err(SyntaxError::NotALiteral(s.span))
         }
     }
 }
 
 impl<'a> ParseFrom<&'a Sexp> for Prim {
-    type Error = String;
+    type Error = SyntaxError;
     fn parse_from(s: &Sexp) -> ParseResult<Prim> {
-        match *s {
-            Sexp::Symbol(ref s) => match &**s {
-                "eq" => Ok(Equal),
-                "le" => Ok(Leq),
-                "add" => Ok(Add), "sub" => Ok(Sub),
-                "mul" => Ok(Mul), "div" => Ok(Div),
-                "print" => Ok(Print),
-                _ => Err(String::from("unrecognized prim"))
-            },
-            _ => Err(String::from("prims must be symbols"))
+        match s.kind {
+            SexpKind::Symbol(ref name) => Prim::from_name(name).ok_or_else(|| {
+                SyntaxError::UnknownPrim(name.clone(), s.span)}),
+            _ => Err(SyntaxError::ExpectedSymbol(s.span))
         }
     }
 }
@@ -1,13 +1,24 @@
 // TODO: exceptions & exception handling
-// TODO: interned strings? Gc'ed strings?
+// TODO: Gc'ed strings?
 
 extern crate regex;
 
-const DEBUG: bool = true;
-
 pub mod parse;
+pub mod asm;
+pub mod bigint;
+pub mod bytecode;
 pub mod cam;
 pub mod compile;
+pub mod debugger;
+pub mod disasm;
+pub mod image;
 pub mod lang;
+pub mod num;
+pub mod opt;
+pub mod peephole;
+pub mod prim;
+pub mod ratio;
 pub mod sexp;
 pub mod string;
+pub mod trace;
+pub mod verify;
@@ -5,18 +5,28 @@ use std::mem;
 
 use std::rc::Rc;
 use std::io;
-use std::io::Write;
+use std::io::{Read,Write};
 use std::str::FromStr;
 use std::borrow::Borrow;
+use std::env;
+use std::fs::File;
+use std::path::Path;
+use std::process;
 
 use cam::cam::{VM,Val,Instr,Proto};
 use cam::compile::compile;
+use cam::debugger::Debugger;
+use cam::disasm::disassemble;
+use cam::image;
 use cam::lang::*;
+use cam::opt;
+use cam::peephole;
 use cam::parse::ParseFrom;
 use cam::sexp::Sexp;
 use cam::string::Str;
+use cam::trace::{Trace,Tracer};
 
-fn repl() {
+fn repl(optimize: bool) {
     let mut stdin = io::stdin();
     let mut stdout = io::stdout();
     let mut line = String::new();
@@ -25,32 +35,182 @@ fn repl() {
         print!("> ");
         stdout.flush().unwrap();
         line.clear();
-        stdin.read_line(&mut line).unwrap();
+        if stdin.read_line(&mut line).unwrap() == 0 {
+            println!("");
+            return
+        }
+        if line.trim().is_empty() { continue }
+
+        // `:debug EXP' runs EXP under the debugger
+        let debugging = line.trim_start().starts_with(":debug");
+        if debugging {
+            line = line.trim_start()[":debug".len()..].to_string()
+        }
 
         // parse s-expression
-        let s = Sexp::from_str(&*line).unwrap();
+        let s = match Sexp::from_str(&*line) {
+            Ok(s) => s,
+            Err(e) => { println!("error: {}", e); continue }
+        };
         println!("SEXP: {}", s);
 
         // parse it into an expression
-        let e = Exp::parse_from(&s).unwrap();
+        let e = match Exp::parse_from(&s) {
+            Ok(e) => e,
+            Err(e) => { print!("{}", e.render(&line)); continue }
+        };
         println!("EXP:  {}", e);
 
+        // optimize it
+        let e = if optimize { opt::optimize(e) } else { e };
+        if optimize { println!("OPT:  {}", e) }
+
         // compile it
         let code = compile(&e);
-        println!("CODE: {:?}", code);
+        let code = if optimize { peephole::optimize(&code) } else { code };
+        print!("CODE:\n{}", disassemble(&code));
+
+        if debugging {
+            if let Err(e) = debug(code, &line) { print!("{}", e) }
+            continue
+        }
 
         // run it
         println!("\nRUNNING:");
-        let val = VM::run(code);
-        println!("VALUE: {:?}", val);
+        match run(code, &line, None) {
+            Ok(val) => println!("VALUE: {}", val),
+            Err(e) => print!("{}", e),
+        }
     }
 }
 
+// Runs `proto', compiled from `src', describing any error with a backtrace.
+fn run(proto: Proto, src: &str, tracer: Option<Box<dyn Tracer>>)
+       -> Result<Val, String>
+{
+    let mut vm = try!(VM::new(proto).map_err(|e| format!("error: {}\n", e)));
+    vm.set_tracer(tracer);
+    match vm.finish() {
+        Ok(()) => Ok(vm.value()),
+        Err(e) => {
+            let mut msg = format!("error: {}\n", e);
+            for frame in vm.backtrace() { msg.push_str(&frame.render(src)) }
+            Err(msg)
+        }
+    }
+}
+
+// Runs `proto', compiled from `src', under the debugger, taking commands
+// from stdin.
+fn debug(proto: Proto, src: &str) -> Result<(), String> {
+    let vm = try!(VM::new(proto).map_err(|e| format!("error: {}\n", e)));
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    Debugger::new(vm, src).run(stdin.lock(), &mut stdout)
+        .map(|_| ()).map_err(|e| format!("error: {}\n", e))
+}
+
+// Compiles a whole source file.
+fn build(src: &str, optimize: bool) -> Result<Proto, String> {
+    let s = try!(Sexp::from_str(src).map_err(|e| format!("error: {}\n", e)));
+    let e = try!(Exp::parse_from(&s).map_err(|e| e.render(src)));
+    let e = if optimize { opt::optimize(e) } else { e };
+    let code = compile(&e);
+    Ok(if optimize { peephole::optimize(&code) } else { code })
+}
+
+fn compile_file(src_path: &str, out_path: &str, optimize: bool)
+                -> Result<(), String>
+{
+    let mut src = String::new();
+    try!(File::open(src_path).and_then(|mut f| f.read_to_string(&mut src))
+         .map_err(|e| format!("{}: {}\n", src_path, e)));
+    let proto = try!(build(&src, optimize)
+                     .map_err(|e| format!("{}: {}", src_path, e)));
+    File::create(out_path).and_then(|mut f| image::save(&proto, &mut f))
+        .map_err(|e| format!("{}: {}\n", out_path, e))
+}
+
+// Loads a compiled file, or compiles a source file. Source files keep their
+// debug info, so we return their text too; compiled ones don't.
+fn load_file(path: &str, optimize: bool) -> Result<(Proto, String), String> {
+    if path.ends_with(".camb") {
+        return File::open(path).map_err(image::LoadError::Io)
+            .and_then(|mut f| image::load(&mut f))
+            .map(|proto| (proto, String::new()))
+            .map_err(|e| format!("{}: {}\n", path, e))
+    }
+    let mut src = String::new();
+    try!(File::open(path).and_then(|mut f| f.read_to_string(&mut src))
+         .map_err(|e| format!("{}: {}\n", path, e)));
+    let proto = try!(build(&src, optimize)
+                     .map_err(|e| format!("{}: {}", path, e)));
+    Ok((proto, src))
+}
+
+fn run_file(path: &str, optimize: bool, tracer: Option<Box<dyn Tracer>>)
+            -> Result<(), String>
+{
+    let (proto, src) = try!(load_file(path, optimize));
+    let val = try!(run(proto, &src, tracer)
+                   .map_err(|e| format!("{}: {}", path, e)));
+    println!("{}", val);
+    Ok(())
+}
+
+fn debug_file(path: &str, optimize: bool) -> Result<(), String> {
+    let (proto, src) = try!(load_file(path, optimize));
+    debug(proto, &src).map_err(|e| format!("{}: {}", path, e))
+}
+
+fn disasm_file(path: &str, optimize: bool) -> Result<(), String> {
+    let (proto, _) = try!(load_file(path, optimize));
+    print!("{}", disassemble(&proto));
+    Ok(())
+}
+
+fn usage() -> Result<(), String> {
+    Err(String::from("usage: cam [--no-opt]\n\
+                      \x20      cam [--no-opt] compile FILE.cam [-o OUT]\n\
+                      \x20      cam [--no-opt] run [--trace[-calls]] FILE\n\
+                      \x20      cam [--no-opt] disasm FILE\n\
+                      \x20      cam [--no-opt] debug FILE\n"))
+}
+
 fn main() {
-    println!("align_of(Instr) = {}", mem::align_of::<Instr>());
-    println!("size_of(Instr)  = {}", mem::size_of::<Instr>());
-    println!("size_of(Prim)   = {}", mem::size_of::<Prim>());
-    println!("size_of(Lit)    = {}", mem::size_of::<Lit>());
-    println!("size_of(Rc<Proto>) = {}", mem::size_of::<Rc<Proto>>());
-    repl();
+    // --no-opt compiles expressions as written, for comparison.
+    let args: Vec<String> = env::args().skip(1).collect();
+    let optimize = !args.iter().any(|a| a == "--no-opt");
+    let args: Vec<&str> = args.iter().map(|a| &**a)
+                              .filter(|&a| a != "--no-opt").collect();
+    let result = match &args[..] {
+        [] => {
+            println!("align_of(Instr) = {}", mem::align_of::<Instr>());
+            println!("size_of(Instr)  = {}", mem::size_of::<Instr>());
+            println!("size_of(Prim)   = {}", mem::size_of::<Prim>());
+            println!("size_of(Lit)    = {}", mem::size_of::<Lit>());
+            println!("size_of(Rc<Proto>) = {}", mem::size_of::<Rc<Proto>>());
+            repl(optimize);
+            Ok(())
+        }
+        ["compile", src] => {
+            let out = Path::new(src).with_extension("camb");
+            compile_file(src, &out.to_string_lossy(), optimize)
+        }
+        ["compile", src, "-o", out] => compile_file(src, out, optimize),
+        ["run", path] => run_file(path, optimize, None),
+        // Traces go to stderr, to keep them apart from the program's value.
+        ["run", "--trace", path] =>
+            run_file(path, optimize, Some(Box::new(Trace::all(io::stderr())))),
+        ["run", "--trace-calls", path] =>
+            run_file(path, optimize,
+                     Some(Box::new(Trace::calls(io::stderr())))),
+        ["disasm", path] => disasm_file(path, optimize),
+        ["debug", path] => debug_file(path, optimize),
+        _ => usage(),
+    };
+    if let Err(e) = result {
+        let _ = io::stderr().write_all(e.as_bytes());
+        process::exit(1)
+    }
 }
@@ -0,0 +1,220 @@
+// Arithmetic on numeric literals.
+//
+// The numeric tower is integers < rationals < floats. Operations on arguments
+// of mixed kinds first convert them to the most general kind among them.
+//
+// Integers are fixnums (Lit::Int) which overflow into bignums (Lit::Big). An
+// integer result that fits in a fixnum is always a fixnum, and a rational
+// result with denominator 1 is always an integer, so representations never
+// overlap.
+use std::cmp::Ordering;
+use std::fmt;
+use std::rc::Rc;
+
+use bigint::BigInt;
+use lang::Lit;
+use ratio::Ratio;
+
+pub fn is_number(l: &Lit) -> bool {
+    match *l { Lit::Int(_) | Lit::Big(_) | Lit::Ratio(_) | Lit::Float(_) => true,
+               _ => false }
+}
+
+pub fn is_exact(l: &Lit) -> bool {
+    match *l { Lit::Int(_) | Lit::Big(_) | Lit::Ratio(_) => true, _ => false }
+}
+
+pub fn from_big(b: BigInt) -> Lit {
+    match b.to_i64() { Some(x) => Lit::Int(x), None => Lit::Big(Rc::new(b)) }
+}
+
+pub fn from_ratio(r: Ratio) -> Lit {
+    if r.is_integer() { from_big(r.numer().clone()) }
+    else { Lit::Ratio(Rc::new(r)) }
+}
+
+fn to_big(l: &Lit) -> BigInt {
+    match *l { Lit::Int(x) => BigInt::from_i64(x),
+               Lit::Big(ref b) => (**b).clone(),
+               _ => panic!("non-integer value") }
+}
+
+fn to_ratio(l: &Lit) -> Ratio {
+    match *l { Lit::Ratio(ref r) => (**r).clone(),
+               _ => Ratio::from_int(to_big(l)) }
+}
+
+fn to_float(l: &Lit) -> f64 {
+    match *l { Lit::Int(x) => x as f64,
+               Lit::Big(ref b) => b.to_f64(),
+               Lit::Ratio(ref r) => r.to_f64(),
+               Lit::Float(x) => x,
+               _ => panic!("non-numeric value") }
+}
+
+// Both arguments of a binary operation, converted to a common kind.
+enum Args {
+    Ints(i64, i64), Bigs(BigInt, BigInt), Ratios(Ratio, Ratio),
+    Floats(f64, f64),
+}
+use self::Args::*;
+
+fn level(l: &Lit) -> u8 {
+    match *l { Lit::Int(_) => 0, Lit::Big(_) => 1, Lit::Ratio(_) => 2,
+               Lit::Float(_) => 3, _ => panic!("non-numeric value") }
+}
+
+fn args(a: &Lit, b: &Lit) -> Args {
+    match (a, b) {
+        (&Lit::Int(x), &Lit::Int(y)) => Ints(x, y),
+        _ => match level(a).max(level(b)) {
+            0 | 1 => Bigs(to_big(a), to_big(b)),
+            2 => Ratios(to_ratio(a), to_ratio(b)),
+            _ => Floats(to_float(a), to_float(b)),
+        }
+    }
+}
+
+// Fixnum operations return None on overflow, whereupon we retry with bignums.
+fn arith(a: &Lit, b: &Lit,
+         fix: fn(i64, i64) -> Option<i64>,
+         big: fn(&BigInt, &BigInt) -> BigInt,
+         ratio: fn(&Ratio, &Ratio) -> Ratio,
+         float: fn(f64, f64) -> f64) -> Lit
+{
+    match args(a, b) {
+        Ints(x, y) => match fix(x, y) {
+            Some(z) => Lit::Int(z),
+            None => from_big(big(&BigInt::from_i64(x), &BigInt::from_i64(y))),
+        },
+        Bigs(x, y) => from_big(big(&x, &y)),
+        Ratios(x, y) => from_ratio(ratio(&x, &y)),
+        Floats(x, y) => Lit::Float(float(x, y)),
+    }
+}
+
+pub fn add(a: &Lit, b: &Lit) -> Lit {
+    arith(a, b, i64::checked_add, BigInt::add, Ratio::add, |x, y| x + y)
+}
+pub fn sub(a: &Lit, b: &Lit) -> Lit {
+    arith(a, b, i64::checked_sub, BigInt::sub, Ratio::sub, |x, y| x - y)
+}
+pub fn mul(a: &Lit, b: &Lit) -> Lit {
+    arith(a, b, i64::checked_mul, BigInt::mul, Ratio::mul, |x, y| x * y)
+}
+// Dividing integers that don't divide evenly gives a rational. Exact division
+// by zero panics.
+pub fn div(a: &Lit, b: &Lit) -> Lit {
+    match args(a, b) {
+        Ints(x, y) if y != 0 && x.checked_rem(y) == Some(0) =>
+            match x.checked_div(y) {
+                Some(z) => Lit::Int(z),
+                None => from_big(BigInt::from_i64(x).div_rem(
+                    &BigInt::from_i64(y)).0),
+            },
+        Ints(x, y) => from_ratio(Ratio::new(BigInt::from_i64(x),
+                                            BigInt::from_i64(y))),
+        Bigs(x, y) => from_ratio(Ratio::new(x, y)),
+        Ratios(x, y) => from_ratio(x.div(&y)),
+        Floats(x, y) => Lit::Float(x / y),
+    }
+}
+
+// Like args, but exact when comparing an exact number with a float. Finite
+// floats convert exactly to rationals, whereas converting the exact number
+// to a float could round. Every exact number lies strictly between -inf and
+// inf, so against those (and NaN) any finite float will do in its place.
+fn cmp_args(a: &Lit, b: &Lit) -> Args {
+    match (a, b) {
+        (&Lit::Float(x), _) if is_exact(b) => match Ratio::from_f64(x) {
+            Some(x) => Ratios(x, to_ratio(b)),
+            None => Floats(x, 0.0),
+        },
+        (_, &Lit::Float(y)) if is_exact(a) => match Ratio::from_f64(y) {
+            Some(y) => Ratios(to_ratio(a), y),
+            None => Floats(0.0, y),
+        },
+        _ => args(a, b),
+    }
+}
+
+// Numeric comparisons. Unlike Lit's Eq and Ord, these compare by value across
+// kinds, and NaN is unequal & incomparable to everything.
+pub fn equal(a: &Lit, b: &Lit) -> bool {
+    match cmp_args(a, b) { Ints(x, y) => x == y, Bigs(x, y) => x == y,
+                           Ratios(x, y) => x == y, Floats(x, y) => x == y }
+}
+pub fn leq(a: &Lit, b: &Lit) -> bool {
+    match cmp_args(a, b) { Ints(x, y) => x <= y, Bigs(x, y) => x <= y,
+                           Ratios(x, y) => x <= y, Floats(x, y) => x <= y }
+}
+
+// Exact numbers are totally ordered by value.
+pub fn cmp_exact(a: &Lit, b: &Lit) -> Ordering {
+    match args(a, b) { Ints(x, y) => x.cmp(&y), Bigs(x, y) => x.cmp(&y),
+                       Ratios(x, y) => x.cmp(&y),
+                       Floats(..) => panic!("inexact number") }
+}
+
+// Rounding. These are the identity on integers.
+fn round_with(a: &Lit, ratio: fn(&Ratio) -> BigInt, float: fn(f64) -> f64)
+              -> Lit
+{
+    match *a { Lit::Int(_) | Lit::Big(_) => a.clone(),
+               Lit::Ratio(ref r) => from_big(ratio(r)),
+               Lit::Float(x) => Lit::Float(float(x)),
+               _ => panic!("non-numeric value") }
+}
+pub fn floor(a: &Lit) -> Lit { round_with(a, Ratio::floor, f64::floor) }
+pub fn ceiling(a: &Lit) -> Lit { round_with(a, Ratio::ceiling, f64::ceil) }
+pub fn truncate(a: &Lit) -> Lit { round_with(a, Ratio::truncate, f64::trunc) }
+// Rounds to even, as in Scheme.
+pub fn round(a: &Lit) -> Lit {
+    round_with(a, Ratio::round, f64::round_ties_even)
+}
+
+// The square root of an exact number is exact if its numerator and
+// denominator are both perfect squares, as for 16 or 1/4.
+pub fn sqrt(a: &Lit) -> Lit {
+    if is_exact(a) {
+        let r = to_ratio(a);
+        if !r.numer().is_negative() {
+            let (n, d) = (r.numer().isqrt(), r.denom().isqrt());
+            if n.mul(&n) == *r.numer() && d.mul(&d) == *r.denom() {
+                return from_ratio(Ratio::new(n, d))
+            }
+        }
+    }
+    Lit::Float(to_float(a).sqrt())
+}
+
+pub fn exact_to_inexact(a: &Lit) -> Lit { Lit::Float(to_float(a)) }
+
+pub fn inexact_to_exact(a: &Lit) -> Lit {
+    match *a {
+        Lit::Float(x) => match Ratio::from_f64(x) {
+            Some(r) => from_ratio(r),
+            None => panic!("no exact representation of {}", a),
+        },
+        _ if is_number(a) => a.clone(),
+        _ => panic!("non-numeric value"),
+    }
+}
+
+// Of a number in lowest terms. For floats, this is the (inexact) numerator or
+// denominator of the float's exact value.
+fn ratio_part(a: &Lit, part: fn(&Ratio) -> &BigInt) -> Lit {
+    match *a {
+        Lit::Float(_) => exact_to_inexact(&ratio_part(&inexact_to_exact(a), part)),
+        _ => from_big(part(&to_ratio(a)).clone()),
+    }
+}
+pub fn numerator(a: &Lit) -> Lit { ratio_part(a, Ratio::numer) }
+pub fn denominator(a: &Lit) -> Lit { ratio_part(a, Ratio::denom) }
+
+// Prints floats so that they read back as floats, not integers.
+pub fn fmt_float(x: f64, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    if x.is_nan() { f.write_str("nan") }
+    else if x.is_infinite() { f.write_str(if x > 0.0 { "inf" } else { "-inf" }) }
+    else { write!(f, "{:?}", x) }
+}
@@ -0,0 +1,261 @@
+// Simplifications on expressions, run between parsing and compiling. None of
+// them may change what a program does, including which errors it raises.
+use std::rc::Rc;
+
+use cam::Val;
+use lang::*;
+use prim;
+use sexp::Span;
+
+pub fn optimize(e: Exp) -> Exp {
+    // Inlining can leave literal arguments for folding to finish off, and
+    // let-bound functions with no uses left for it to drop.
+    let mut inliner = Inliner { scope: vec![], budget: size(&e) + INLINE_SLACK };
+    fold(inliner.inline(e))
+}
+
+// Folds prim applications to literal arguments, picks the branch of an if with
+// a literal condition, and drops let bindings that are pure and unused.
+pub fn fold(e: Exp) -> Exp {
+    match e {
+        Exp::Lit(_) | Exp::Var(..) => e,
+        Exp::Lam(ids, body) => Exp::Lam(ids, Box::new(fold(*body))),
+        Exp::Set(id, index, exp) => Exp::Set(id, index, Box::new(fold(*exp))),
+        Exp::App(func, args, span) => {
+            let func = fold(*func);
+            let args: Vec<Exp> = args.into_iter().map(fold).collect();
+            match fold_app(&func, &args) {
+                Some(l) => Exp::Lit(l),
+                None => Exp::App(Box::new(func), args, span),
+            }
+        }
+        Exp::If(cnd, thn, els) => match fold(*cnd) {
+            Exp::Lit(l) => fold(if l.truthy() { *thn } else { *els }),
+            cnd => Exp::If(Box::new(cnd), Box::new(fold(*thn)),
+                           Box::new(fold(*els))),
+        },
+        Exp::Let(binds, body) => {
+            let binds = binds.into_iter().map(|(id, e)| (id, fold(e))).collect();
+            drop_unused(binds, fold(*body))
+        }
+    }
+}
+
+fn fold_app(func: &Exp, args: &[Exp]) -> Option<Lit> {
+    let p = match *func { Exp::Lit(Lit::Prim(p)) => p, _ => return None };
+    // Print is run for its effect.
+    if p == Print || p.arity() as usize != args.len() { return None }
+    let mut vals = vec![];
+    for arg in args {
+        match *arg {
+            Exp::Lit(ref l) => vals.push(Val::Lit(l.clone())),
+            _ => return None,
+        }
+    }
+    // Errors are left to happen at runtime. Results that aren't literals (like
+    // pairs) can't be put back into the program.
+    match prim::apply(p, &vals) { Ok(Val::Lit(l)) => Some(l), _ => None }
+}
+
+fn drop_unused(binds: Vec<(Ident,Exp)>, mut body: Exp) -> Exp {
+    let mut kept = vec![];
+    // Going from the innermost binding outwards, a binding's index in the body
+    // is the number of bindings after it that we're keeping.
+    for (id, e) in binds.into_iter().rev() {
+        let index = kept.len() as VarIndex;
+        if is_pure(&e) && !uses(&body, index) {
+            body = shift(body, index + 1, -1);
+        } else {
+            kept.push((id, e));
+        }
+    }
+    if kept.is_empty() { return body }
+    kept.reverse();
+    Exp::Let(kept, Box::new(body))
+}
+
+// Can evaluating `e' have no effect, and never fail?
+fn is_pure(e: &Exp) -> bool {
+    match *e { Exp::Lit(_) | Exp::Var(..) | Exp::Lam(..) => true, _ => false }
+}
+
+// Does `e' refer to the variable with index `var'?
+fn uses(e: &Exp, var: VarIndex) -> bool {
+    match *e {
+        Exp::Lit(_) => false,
+        Exp::Var(_, index) => index == var,
+        Exp::Set(_, index, ref exp) => index == var || uses(exp, var),
+        Exp::Lam(ref ids, ref body) => uses(body, var + ids.len() as VarIndex),
+        Exp::App(ref func, ref args, _) =>
+            uses(func, var) || args.iter().any(|a| uses(a, var)),
+        Exp::If(ref cnd, ref thn, ref els) =>
+            uses(cnd, var) || uses(thn, var) || uses(els, var),
+        Exp::Let(ref binds, ref body) =>
+            binds.iter().any(|b| uses(&b.1, var))
+            || uses(body, var + binds.len() as VarIndex),
+    }
+}
+
+// Adds `by' to the index of every variable in `e' whose index is at least
+// `cutoff', i.e. that is bound outside the innermost `cutoff' variables.
+pub fn shift(e: Exp, cutoff: VarIndex, by: i64) -> Exp {
+    let adjust = |index: VarIndex| {
+        if index < cutoff { index } else { (index as i64 + by) as VarIndex }
+    };
+    match e {
+        Exp::Lit(_) => e,
+        Exp::Var(id, index) => Exp::Var(id, adjust(index)),
+        Exp::Set(id, index, exp) =>
+            Exp::Set(id, adjust(index), Box::new(shift(*exp, cutoff, by))),
+        Exp::Lam(ids, body) => {
+            let n = ids.len() as VarIndex;
+            Exp::Lam(ids, Box::new(shift(*body, cutoff + n, by)))
+        }
+        Exp::App(func, args, span) =>
+            Exp::App(Box::new(shift(*func, cutoff, by)),
+                     args.into_iter().map(|a| shift(a, cutoff, by)).collect(),
+                     span),
+        Exp::If(cnd, thn, els) =>
+            Exp::If(Box::new(shift(*cnd, cutoff, by)),
+                    Box::new(shift(*thn, cutoff, by)),
+                    Box::new(shift(*els, cutoff, by))),
+        Exp::Let(binds, body) => {
+            let n = binds.len() as VarIndex;
+            let binds = binds.into_iter()
+                .map(|(id, e)| (id, shift(e, cutoff, by))).collect();
+            Exp::Let(binds, Box::new(shift(*body, cutoff + n, by)))
+        }
+    }
+}
+
+// ---------- Inlining ----------
+// Functions whose bodies are at most this big are inlined at their call sites.
+const INLINE_SIZE: usize = 30;
+// Inlining may grow a program by at most its own size plus this much.
+const INLINE_SLACK: usize = 100;
+
+// The number of nodes in an expression.
+pub fn size(e: &Exp) -> usize {
+    1 + match *e {
+        Exp::Lit(_) | Exp::Var(..) => 0,
+        Exp::Set(_, _, ref exp) => size(exp),
+        Exp::Lam(_, ref body) => size(body),
+        Exp::App(ref func, ref args, _) =>
+            size(func) + args.iter().map(size).sum::<usize>(),
+        Exp::If(ref cnd, ref thn, ref els) => size(cnd) + size(thn) + size(els),
+        Exp::Let(ref binds, ref body) =>
+            binds.iter().map(|b| size(&b.1)).sum::<usize>() + size(body),
+    }
+}
+
+// Is the variable with index `var' ever set! in `e'?
+fn assigned(e: &Exp, var: VarIndex) -> bool {
+    match *e {
+        Exp::Lit(_) | Exp::Var(..) => false,
+        Exp::Set(_, index, ref exp) => index == var || assigned(exp, var),
+        Exp::Lam(ref ids, ref body) =>
+            assigned(body, var + ids.len() as VarIndex),
+        Exp::App(ref func, ref args, _) =>
+            assigned(func, var) || args.iter().any(|a| assigned(a, var)),
+        Exp::If(ref cnd, ref thn, ref els) =>
+            assigned(cnd, var) || assigned(thn, var) || assigned(els, var),
+        Exp::Let(ref binds, ref body) =>
+            binds.iter().any(|b| assigned(&b.1, var))
+            || assigned(body, var + binds.len() as VarIndex),
+    }
+}
+
+// Applying a lambda directly is the same as binding its parameters with let.
+fn beta(ids: Vec<Ident>, body: Exp, args: Vec<Exp>) -> Exp {
+    Exp::Let(ids.into_iter().zip(args).collect(), Box::new(body))
+}
+
+struct Inliner {
+    // For each variable in scope, innermost last: if it is bound by let to a
+    // function we may inline, that function and the length of the scope it
+    // was defined in.
+    scope: Vec<Option<(Rc<Exp>, usize)>>,
+    // How many more nodes inlining may add.
+    budget: usize,
+}
+
+impl Inliner {
+    fn inline(&mut self, e: Exp) -> Exp {
+        match e {
+            Exp::Lit(_) | Exp::Var(..) => e,
+            Exp::Set(id, index, exp) =>
+                Exp::Set(id, index, Box::new(self.inline(*exp))),
+            Exp::Lam(ids, body) => {
+                let body = self.inline_scoped(ids.len(), |s| s.inline(*body));
+                Exp::Lam(ids, Box::new(body))
+            }
+            Exp::App(func, args, span) => match *func {
+                Exp::Lam(ids, body) if ids.len() == args.len() =>
+                    self.inline(beta(ids, *body, args)),
+                func => {
+                    let func = self.inline(func);
+                    let args: Vec<Exp> =
+                        args.into_iter().map(|a| self.inline(a)).collect();
+                    self.inline_call(func, args, span)
+                }
+            },
+            Exp::If(cnd, thn, els) =>
+                Exp::If(Box::new(self.inline(*cnd)), Box::new(self.inline(*thn)),
+                        Box::new(self.inline(*els))),
+            Exp::Let(binds, body) => {
+                let binds: Vec<(Ident,Exp)> = binds.into_iter()
+                    .map(|(id, e)| (id, self.inline(e))).collect();
+                // A let-bound function is known at its call sites so long as
+                // the variable is never set!.
+                let n = binds.len();
+                let def_len = self.scope.len();
+                for (i, &(_, ref e)) in binds.iter().enumerate() {
+                    let var = (n - 1 - i) as VarIndex;
+                    self.scope.push(match *e {
+                        Exp::Lam(_, ref lam_body)
+                            if size(lam_body) <= INLINE_SIZE
+                            && !assigned(&body, var) =>
+                            Some((Rc::new(e.clone()), def_len)),
+                        _ => None,
+                    });
+                }
+                let body = self.inline(*body);
+                self.scope.truncate(def_len);
+                Exp::Let(binds, Box::new(body))
+            }
+        }
+    }
+
+    fn inline_scoped<F>(&mut self, n: usize, f: F) -> Exp
+        where F: FnOnce(&mut Inliner) -> Exp
+    {
+        let len = self.scope.len();
+        self.scope.extend((0..n).map(|_| None));
+        let e = f(self);
+        self.scope.truncate(len);
+        e
+    }
+
+    fn inline_call(&mut self, func: Exp, args: Vec<Exp>, span: Span) -> Exp {
+        let known = match func {
+            Exp::Var(_, index) =>
+                self.scope[self.scope.len() - 1 - index as usize].clone(),
+            _ => None,
+        };
+        if let Some((lam, def_len)) = known {
+            if let Exp::Lam(ref ids, ref body) = *lam {
+                let cost = size(body);
+                if ids.len() == args.len() && cost <= self.budget {
+                    self.budget -= cost;
+                    // The function's free variables were bound outside
+                    // everything bound since its definition.
+                    let by = (self.scope.len() - def_len) as i64;
+                    return beta(ids.clone(), shift((**body).clone(),
+                                                   ids.len() as VarIndex, by),
+                                args)
+                }
+            }
+        }
+        Exp::App(Box::new(func), args, span)
+    }
+}
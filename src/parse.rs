@@ -1,4 +1,4 @@
-pub trait ParseFrom<Src> {
+pub trait ParseFrom<Src>: Sized {
     type Error;
     fn parse_from(s: Src) -> Result<Self, Self::Error>;
 }
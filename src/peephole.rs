@@ -0,0 +1,179 @@
+// Local rewrites of compiled code: threading jumps through chains of jumps
+// and into returns, simplifying ifs whose branches go to the same place or do
+// the same thing, and dropping unreachable instructions and jumps to the next
+// instruction.
+use std::rc::Rc;
+
+use cam::*;
+
+pub fn optimize(proto: &Proto) -> Proto {
+    let (code, map) = optimize_mapped(proto.instrs());
+    let mut out = Proto::assemble(proto.arity, &code);
+    out.debug = proto.debug.as_ref().map(|debug| remap(debug, &map));
+    out
+}
+
+pub fn optimize_code(code: Code) -> Code {
+    optimize_mapped(code).0
+}
+
+// Also returns where each old index (and the end of the code) ended up.
+fn optimize_mapped(code: Code) -> (Code, Vec<usize>) {
+    let mut map: Vec<usize> = (0..code.len() + 1).collect();
+    let mut code: Code = code.into_iter().map(optimize_instr).collect();
+    loop {
+        let len = code.len();
+        thread(&mut code);
+        let (new_code, new_index) = rebuild(code);
+        code = new_code;
+        for m in &mut map { *m = new_index[*m] }
+        if code.len() == len { return (code, map) }
+    }
+}
+
+// Moves let ranges and spans to follow their instructions, dropping any left
+// empty or whose instructions are gone.
+fn remap(debug: &DebugInfo, map: &[usize]) -> DebugInfo {
+    let lets = debug.lets.iter().filter_map(|l| {
+        let (start, end) = (map[l.start], map[l.end]);
+        if start >= end { return None }
+        Some(LetRange { ids: l.ids.clone(), start: start, end: end })
+    }).collect();
+    // Dropped instructions take up no room in the new code.
+    let spans = debug.spans.iter().filter(|s| map[s.0] < map[s.0 + 1])
+        .map(|&(i, span)| (map[i], span)).collect();
+    DebugInfo { name: debug.name.clone(), scope: debug.scope.clone(),
+                lets: lets, spans: spans }
+}
+
+// Optimizes the code of closures, too.
+fn optimize_instr(instr: Instr) -> Instr {
+    match instr {
+        Instr::Closure(proto) => Instr::Closure(Rc::new(optimize(&proto))),
+        instr => instr,
+    }
+}
+
+// Where control really ends up when it reaches `index'.
+fn resolve(code: &Code, mut index: InstrIndex) -> InstrIndex {
+    // A cycle of jumps would loop forever anyway; give up on it.
+    for _ in 0..code.len() {
+        match code[index as usize] {
+            Instr::Jump(next) => index = next,
+            _ => break,
+        }
+    }
+    index
+}
+
+fn thread(code: &mut Code) {
+    for i in 0..code.len() {
+        let new = match code[i] {
+            Instr::Jump(target) => {
+                let target = resolve(code, target);
+                match code[target as usize] {
+                    Instr::Return => Instr::Return,
+                    _ => Instr::Jump(target),
+                }
+            }
+            Instr::If(thn, els) => {
+                let (thn, els) = (resolve(code, thn), resolve(code, els));
+                // Either branch will do; rebuild pops the condition.
+                if same_branches(code, thn, els) { Instr::If(thn, thn) }
+                else { Instr::If(thn, els) }
+            }
+            _ => continue,
+        };
+        code[i] = new;
+    }
+}
+
+// Whether the code from `a' and from `b' does the same thing, up to the point
+// they join or return.
+fn same_branches(code: &Code, mut a: InstrIndex, mut b: InstrIndex) -> bool {
+    for _ in 0..code.len() {
+        a = resolve(code, a);
+        b = resolve(code, b);
+        if a == b { return true }
+        match (&code[a as usize], &code[b as usize]) {
+            (&Instr::Return, &Instr::Return) => return true,
+            (&Instr::TailApply(m), &Instr::TailApply(n)) => return m == n,
+            // Ifs that go the same way join; others we don't look into.
+            (&Instr::If(t1, e1), &Instr::If(t2, e2)) =>
+                return resolve(code, t1) == resolve(code, t2)
+                    && resolve(code, e1) == resolve(code, e2),
+            (x, y) if same_instr(x, y) => {}
+            _ => return false,
+        }
+        a += 1;
+        b += 1;
+    }
+    false
+}
+
+// Whether two straight-line instructions are the same.
+fn same_instr(a: &Instr, b: &Instr) -> bool {
+    use cam::Instr::*;
+    match (a, b) {
+        (&Get(m), &Get(n)) | (&Set(m), &Set(n)) | (&BoxVar(m), &BoxVar(n))
+            | (&GetBox(m), &GetBox(n)) | (&SetBox(m), &SetBox(n))
+            | (&Bind(m), &Bind(n)) | (&Unbind(m), &Unbind(n)) => m == n,
+        (&Apply(m), &Apply(n)) => m == n,
+        (&Push(ref x), &Push(ref y)) => x == y,
+        (&Pop, &Pop) => true,
+        (&Prim(p), &Prim(q)) => p == q,
+        (&Closure(ref p), &Closure(ref q)) => Rc::ptr_eq(p, q),
+        _ => false,
+    }
+}
+
+fn successors(code: &Code, i: usize) -> Vec<usize> {
+    match code[i] {
+        Instr::Jump(target) => vec![target as usize],
+        Instr::If(thn, els) => vec![thn as usize, els as usize],
+        Instr::Return | Instr::TailApply(_) => vec![],
+        _ => vec![i + 1],
+    }
+}
+
+// Returns the new code and the new index of each old one.
+fn rebuild(code: Code) -> (Code, Vec<usize>) {
+    let mut reachable = vec![false; code.len()];
+    let mut todo = vec![0];
+    while let Some(i) = todo.pop() {
+        if i >= code.len() || reachable[i] { continue }
+        reachable[i] = true;
+        todo.extend(successors(&code, i));
+    }
+    // The next reachable instruction at or after each index.
+    let mut next = vec![code.len(); code.len() + 1];
+    for i in (0..code.len()).rev() {
+        next[i] = if reachable[i] { i } else { next[i + 1] };
+    }
+
+    // Emit the reachable instructions, with jump targets still in old indices.
+    let len = code.len();
+    let mut out = vec![];
+    let mut new_index = vec![0; len + 1];
+    for (i, instr) in code.into_iter().enumerate() {
+        new_index[i] = out.len();
+        if !reachable[i] { continue }
+        let target = match instr {
+            Instr::Jump(target) => target,
+            Instr::If(thn, els) if thn == els => { out.push(Instr::Pop); thn }
+            instr => { out.push(instr); continue }
+        };
+        if next[target as usize] != next[i + 1] { out.push(Instr::Jump(target)) }
+    }
+    new_index[len] = out.len();
+
+    let fix = |index: InstrIndex| new_index[index as usize] as InstrIndex;
+    for instr in &mut out {
+        *instr = match *instr {
+            Instr::Jump(target) => Instr::Jump(fix(target)),
+            Instr::If(thn, els) => Instr::If(fix(thn), fix(els)),
+            _ => continue,
+        };
+    }
+    (out, new_index)
+}
@@ -0,0 +1,248 @@
+// Implementations of the primitive operations.
+use std::fmt;
+use std::str::FromStr;
+
+use cam::Val;
+use lang::*;
+use num;
+use parse::ParseFrom;
+use sexp::Sexp;
+use string::{Str,Symbol};
+
+#[derive(Debug)]
+pub enum PrimError {
+    // An argument had the wrong type.
+    Type { prim: Prim, expected: &'static str, got: Val },
+    // An argument had the right type but a value the prim can't handle, such
+    // as an out-of-range index.
+    Domain { prim: Prim, arg: Val },
+    DivideByZero,
+}
+
+impl fmt::Display for PrimError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            PrimError::Type { prim, expected, ref got } =>
+                write!(f, "{}: expected {}, got {}", prim, expected, got),
+            PrimError::Domain { prim, ref arg } =>
+                write!(f, "{}: argument out of range: {}", prim, arg),
+            PrimError::DivideByZero => f.write_str("division by zero"),
+        }
+    }
+}
+
+pub type PrimResult = Result<Val, PrimError>;
+
+fn lit(l: Lit) -> PrimResult { Ok(Val::Lit(l)) }
+
+// ---------- Checking arguments ----------
+fn number(prim: Prim, v: &Val) -> Result<&Lit, PrimError> {
+    match *v {
+        Val::Lit(ref l) if num::is_number(l) => Ok(l),
+        _ => Err(PrimError::Type { prim: prim, expected: "number",
+                                   got: v.clone() }),
+    }
+}
+
+// Exact division by zero panics, so we check for it up front. Dividing a
+// float by zero, or anything by 0.0, gives an infinity or NaN as in IEEE 754.
+fn divisor<'a>(prim: Prim, dividend: &Lit, v: &'a Val)
+               -> Result<&'a Lit, PrimError>
+{
+    match try!(number(prim, v)) {
+        &Lit::Int(0) if num::is_exact(dividend) => Err(PrimError::DivideByZero),
+        l => Ok(l),
+    }
+}
+
+fn string(prim: Prim, v: &Val) -> Result<&Str, PrimError> {
+    match *v {
+        Val::Lit(Lit::String(ref s)) => Ok(s),
+        _ => Err(PrimError::Type { prim: prim, expected: "string",
+                                   got: v.clone() }),
+    }
+}
+
+fn character(prim: Prim, v: &Val) -> Result<char, PrimError> {
+    match *v {
+        Val::Lit(Lit::Char(c)) => Ok(c),
+        _ => Err(PrimError::Type { prim: prim, expected: "character",
+                                   got: v.clone() }),
+    }
+}
+
+fn index(prim: Prim, v: &Val) -> Result<usize, PrimError> {
+    match *v {
+        Val::Lit(Lit::Int(i)) if i >= 0 => Ok(i as usize),
+        Val::Lit(Lit::Int(_)) => Err(PrimError::Domain { prim: prim,
+                                                        arg: v.clone() }),
+        _ => Err(PrimError::Type { prim: prim, expected: "index",
+                                   got: v.clone() }),
+    }
+}
+
+fn list(prim: Prim, v: &Val) -> Result<Vec<Val>, PrimError> {
+    let mut elems = vec![];
+    let mut v = v;
+    loop {
+        match *v {
+            Val::Lit(Lit::Nil) => return Ok(elems),
+            Val::Pair(ref p) => { elems.push(p.0.clone()); v = &p.1 }
+            _ => return Err(PrimError::Type { prim: prim, expected: "list",
+                                              got: v.clone() }),
+        }
+    }
+}
+
+// ---------- Applying prims ----------
+pub fn apply(prim: Prim, args: &[Val]) -> PrimResult {
+    debug_assert!(args.len() == prim.arity() as usize);
+    let numeric = args.iter().all(Val::is_number);
+    match prim {
+        Equal if numeric => lit(Lit::Bool(num::equal(
+            try!(number(prim, &args[0])), try!(number(prim, &args[1]))))),
+        Equal => lit(Lit::Bool(args[0] == args[1])),
+        Leq if numeric => lit(Lit::Bool(num::leq(
+            try!(number(prim, &args[0])), try!(number(prim, &args[1]))))),
+        // NB. uses PartialOrd :(
+        Leq => lit(Lit::Bool(args[0] <= args[1])),
+        Add | Sub | Mul | Div => {
+            let a = try!(number(prim, &args[0]));
+            let b = try!(if prim == Div { divisor(prim, a, &args[1]) }
+                         else { number(prim, &args[1]) });
+            lit(match prim { Add => num::add(a, b), Sub => num::sub(a, b),
+                             Mul => num::mul(a, b), _ => num::div(a, b) })
+        }
+        Floor => lit(num::floor(try!(number(prim, &args[0])))),
+        Ceiling => lit(num::ceiling(try!(number(prim, &args[0])))),
+        Round => lit(num::round(try!(number(prim, &args[0])))),
+        Truncate => lit(num::truncate(try!(number(prim, &args[0])))),
+        Sqrt => lit(num::sqrt(try!(number(prim, &args[0])))),
+        ExactToInexact => lit(num::exact_to_inexact(
+            try!(number(prim, &args[0])))),
+        InexactToExact | Numerator | Denominator => {
+            match try!(number(prim, &args[0])) {
+                &Lit::Float(x) if !x.is_finite() =>
+                    Err(PrimError::Domain { prim: prim, arg: args[0].clone() }),
+                a => lit(match prim { InexactToExact => num::inexact_to_exact(a),
+                                      Numerator => num::numerator(a),
+                                      _ => num::denominator(a) }),
+            }
+        }
+
+        Cons => Ok(Val::cons(args[0].clone(), args[1].clone())),
+        Car | Cdr => match args[0] {
+            Val::Pair(ref p) => Ok(if prim == Car { p.0.clone() }
+                                   else { p.1.clone() }),
+            _ => Err(PrimError::Type { prim: prim, expected: "pair",
+                                       got: args[0].clone() }),
+        },
+        IsNull => lit(Lit::Bool(args[0] == Val::Lit(Lit::Nil))),
+        IsPair => lit(Lit::Bool(match args[0] { Val::Pair(_) => true,
+                                                _ => false })),
+
+        StringAppend => {
+            let mut s = String::from(&**try!(string(prim, &args[0])));
+            s.push_str(try!(string(prim, &args[1])));
+            lit(Lit::String(Str::new(&s)))
+        }
+        StringLength =>
+            lit(Lit::Int(try!(string(prim, &args[0])).chars().count() as i64)),
+        Substring => {
+            let s = try!(string(prim, &args[0]));
+            let start = try!(index(prim, &args[1]));
+            let end = try!(index(prim, &args[2]));
+            if end > s.chars().count() {
+                return Err(PrimError::Domain { prim: prim,
+                                               arg: args[2].clone() })
+            }
+            if start > end {
+                return Err(PrimError::Domain { prim: prim,
+                                               arg: args[1].clone() })
+            }
+            let sub: String = s.chars().skip(start).take(end - start).collect();
+            lit(Lit::String(Str::new(&sub)))
+        }
+        // The needle may be a character or a substring.
+        StringIndex => {
+            let s = try!(string(prim, &args[0]));
+            let found = match args[1] {
+                Val::Lit(Lit::Char(c)) => s.find(c),
+                ref v => s.find(&**try!(string(prim, v))),
+            };
+            lit(match found {
+                Some(i) => Lit::Int(s[..i].chars().count() as i64),
+                None => Lit::Nil,
+            })
+        }
+        // The whole string must be the number, without spaces or comments.
+        StringToNumber => {
+            let s = try!(string(prim, &args[0]));
+            let n = Sexp::from_str(s).ok()
+                .filter(|e| e.span.start == 0 && e.span.end == s.len())
+                .and_then(|e| Lit::parse_from(&e).ok())
+                .and_then(|l| if num::is_number(&l) { Some(l) } else { None });
+            lit(n.unwrap_or(Lit::Bool(false)))
+        }
+        NumberToString => {
+            let n = try!(number(prim, &args[0]));
+            lit(Lit::String(Str::new(&n.to_string())))
+        }
+        StringUpcase =>
+            lit(Lit::String(Str::new(&try!(string(prim, &args[0]))
+                                     .to_uppercase()))),
+        CharToInteger => lit(Lit::Int(try!(character(prim, &args[0])) as i64)),
+        IntegerToChar => {
+            let n = try!(index(prim, &args[0]));
+            match ::std::char::from_u32(n as u32) {
+                Some(c) if n <= u32::max_value() as usize => lit(Lit::Char(c)),
+                _ => Err(PrimError::Domain { prim: prim, arg: args[0].clone() }),
+            }
+        }
+        StringRef => {
+            let s = try!(string(prim, &args[0]));
+            match s.chars().nth(try!(index(prim, &args[1]))) {
+                Some(c) => lit(Lit::Char(c)),
+                None => Err(PrimError::Domain { prim: prim, arg: args[1].clone() }),
+            }
+        }
+        StringToList => {
+            let s = try!(string(prim, &args[0]));
+            Ok(Val::list(s.chars().map(|c| Val::Lit(Lit::Char(c))).collect()))
+        }
+        IsSymbol => lit(Lit::Bool(match args[0] { Val::Lit(Lit::Symbol(_)) => true,
+                                                  _ => false })),
+        SymbolToString => match args[0] {
+            Val::Lit(Lit::Symbol(ref s)) => lit(Lit::String(s.to_str())),
+            _ => Err(PrimError::Type { prim: prim, expected: "symbol",
+                                       got: args[0].clone() }),
+        },
+        StringToSymbol =>
+            lit(Lit::Symbol(Symbol::intern(try!(string(prim, &args[0]))))),
+        StringSplit => {
+            let s = try!(string(prim, &args[0]));
+            let sep = try!(string(prim, &args[1]));
+            // An empty separator splits into characters.
+            let parts: Vec<Val> = if sep.is_empty() {
+                s.chars().map(|c| Val::Lit(Lit::String(Str::new(&c.to_string()))))
+                 .collect()
+            } else {
+                s.split(&**sep).map(|p| Val::Lit(Lit::String(Str::new(p))))
+                 .collect()
+            };
+            Ok(Val::list(parts))
+        }
+        StringJoin => {
+            let parts = try!(list(prim, &args[0]));
+            let sep = try!(string(prim, &args[1]));
+            let strs = try!(parts.iter().map(|p| string(prim, p).map(|s| &**s))
+                            .collect::<Result<Vec<&str>,_>>());
+            lit(Lit::String(Str::new(&strs.join(sep))))
+        }
+
+        Print => {
+            println!("{}", try!(string(prim, &args[0])));
+            lit(Lit::Nil)
+        }
+    }
+}
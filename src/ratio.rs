@@ -0,0 +1,119 @@
+// Exact rational numbers.
+use std::cmp::Ordering;
+use std::fmt;
+
+use bigint::BigInt;
+
+// Always in lowest terms, with a positive denominator.
+#[derive(Clone,PartialEq,Eq,Hash,Debug)]
+pub struct Ratio { num: BigInt, den: BigInt }
+
+impl Ratio {
+    // Panics on a zero denominator.
+    pub fn new(num: BigInt, den: BigInt) -> Ratio {
+        assert!(!den.is_zero(), "division by zero");
+        let g = num.gcd(&den);
+        let (num, den) = (num.div_rem(&g).0, den.div_rem(&g).0);
+        if den.is_negative() { Ratio { num: num.neg(), den: den.neg() } }
+        else { Ratio { num: num, den: den } }
+    }
+
+    pub fn from_int(n: BigInt) -> Ratio { Ratio { num: n, den: BigInt::one() } }
+
+    // Exact conversion from a finite float.
+    pub fn from_f64(x: f64) -> Option<Ratio> {
+        if !x.is_finite() { return None }
+        if x.fract() == 0.0 { return BigInt::from_f64(x).map(Ratio::from_int) }
+        // x = mantissa * 2^exp, with exp < 0 since x isn't integral.
+        let bits = x.abs().to_bits();
+        let biased = (bits >> 52) as i32;
+        let mantissa = bits & ((1 << 52) - 1);
+        let (mantissa, exp) = if biased == 0 { (mantissa, -1074) }
+                              else { (mantissa | 1 << 52, biased - 1075) };
+        let num = BigInt::from_i64(mantissa as i64);
+        let num = if x < 0.0 { num.neg() } else { num };
+        Some(Ratio::new(num, BigInt::one().shl(-exp as usize)))
+    }
+
+    pub fn numer(&self) -> &BigInt { &self.num }
+    pub fn denom(&self) -> &BigInt { &self.den }
+    pub fn is_integer(&self) -> bool { self.den.is_one() }
+
+    pub fn to_f64(&self) -> f64 {
+        // Dividing the f64 approximations overflows for big operands, so
+        // instead take a quotient of at least 64 bits and scale it back down.
+        // If the division wasn't exact, setting the quotient's lowest bit
+        // makes it round as the exact value would.
+        let k = (64 + self.den.bits()).saturating_sub(self.num.bits());
+        let (q, r) = self.num.shl(k).div_rem(&self.den);
+        let even = q.div_rem(&BigInt::from_i64(2)).1.is_zero();
+        let q = if r.is_zero() || !even { q }
+                else if q.is_negative() { q.sub(&BigInt::one()) }
+                else { q.add(&BigInt::one()) };
+        let mut x = q.to_f64();
+        let mut k = k;
+        while k > 0 {
+            let step = if k > 1000 { 1000 } else { k };
+            x /= 2f64.powi(step as i32);
+            k -= step;
+        }
+        x
+    }
+
+    pub fn add(&self, other: &Ratio) -> Ratio {
+        Ratio::new(self.num.mul(&other.den).add(&other.num.mul(&self.den)),
+                   self.den.mul(&other.den))
+    }
+    pub fn sub(&self, other: &Ratio) -> Ratio {
+        Ratio::new(self.num.mul(&other.den).sub(&other.num.mul(&self.den)),
+                   self.den.mul(&other.den))
+    }
+    pub fn mul(&self, other: &Ratio) -> Ratio {
+        Ratio::new(self.num.mul(&other.num), self.den.mul(&other.den))
+    }
+    // Panics on division by zero.
+    pub fn div(&self, other: &Ratio) -> Ratio {
+        Ratio::new(self.num.mul(&other.den), self.den.mul(&other.num))
+    }
+
+    // Rounding to integers.
+    pub fn truncate(&self) -> BigInt { self.num.div_rem(&self.den).0 }
+    pub fn floor(&self) -> BigInt {
+        let (q, r) = self.num.div_rem(&self.den);
+        if r.is_negative() { q.sub(&BigInt::one()) } else { q }
+    }
+    pub fn ceiling(&self) -> BigInt {
+        let (q, r) = self.num.div_rem(&self.den);
+        if !r.is_negative() && !r.is_zero() { q.add(&BigInt::one()) } else { q }
+    }
+    // Rounds to even.
+    pub fn round(&self) -> BigInt {
+        let (q, r) = self.num.div_rem(&self.den);
+        let away = if self.num.is_negative() { q.sub(&BigInt::one()) }
+                   else { q.add(&BigInt::one()) };
+        match r.abs().shl(1).cmp(&self.den) {
+            Ordering::Less => q,
+            Ordering::Greater => away,
+            Ordering::Equal =>
+                if q.div_rem(&BigInt::from_i64(2)).1.is_zero() { q }
+                else { away },
+        }
+    }
+}
+
+impl Ord for Ratio {
+    fn cmp(&self, other: &Ratio) -> Ordering {
+        self.num.mul(&other.den).cmp(&other.num.mul(&self.den))
+    }
+}
+impl PartialOrd for Ratio {
+    fn partial_cmp(&self, other: &Ratio) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Ratio {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{}/{}", self.num, self.den)
+    }
+}
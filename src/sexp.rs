@@ -1,44 +1,126 @@
-use std::collections::{HashMap};
 use std::str::FromStr;
 use std::fmt::{Display,Formatter};
 use std::fmt;
 
 use regex::Regex;
 
-use string::Str;
+use bigint::BigInt;
+use num;
+use string::{Str,Symbol};
+
+// A range of byte offsets into the source text.
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub struct Span { pub start: usize, pub end: usize }
+
+#[derive(Clone,Debug)]
+pub struct Sexp { pub kind: SexpKind, pub span: Span }
 
 #[derive(Clone,Debug)]
-pub enum Sexp {
+pub enum SexpKind {
     Int(i64),
+    Big(BigInt),
+    // as written; not necessarily in lowest terms.
+    Ratio(BigInt, BigInt),
+    Float(f64),
+    Char(char),
     String(Str),
-    Symbol(Str),
+    Symbol(Symbol),
     List(Vec<Sexp>),
 }
 
+impl Sexp {
+    pub fn new(kind: SexpKind, span: Span) -> Sexp {
+        Sexp { kind: kind, span: span }
+    }
+}
+
 impl Display for Sexp {
     fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
-        match *self {
-            Sexp::Int(i) => i.fmt(f),
-            Sexp::String(ref s) => write!(f, "{:?}", s as &str),
-            Sexp::Symbol(ref s) => s.fmt(f),
-            Sexp::List(ref v) => {
+        match self.kind {
+            SexpKind::Int(i) => i.fmt(f),
+            SexpKind::Big(ref b) => b.fmt(f),
+            SexpKind::Ratio(ref n, ref d) => write!(f, "{}/{}", n, d),
+            SexpKind::Float(x) => num::fmt_float(x, f),
+            SexpKind::Char(c) => fmt_char(c, f),
+            SexpKind::String(ref s) => write!(f, "{:?}", s as &str),
+            SexpKind::Symbol(ref s) => s.fmt(f),
+            SexpKind::List(ref v) => {
                 try!(write!(f, "("));
-                if !v.is_empty() { try!(v[0].fmt(f)) }
-                for e in &v[1..] { try!(write!(f, " {}", e)) }
+                if !v.is_empty() {
+                    try!(v[0].fmt(f));
+                    for e in &v[1..] { try!(write!(f, " {}", e)) }
+                }
                 write!(f, ")")
             }
         }
     }
 }
 
+impl Span {
+    // The line and column the span starts on, counting from 1.
+    pub fn line_col(&self, src: &str) -> (usize, usize) {
+        let start = self.start.min(src.len());
+        let line_start = src[..start].rfind('\n').map_or(0, |i| i + 1);
+        (src[..start].matches('\n').count() + 1,
+         src[line_start..start].chars().count() + 1)
+    }
+
+    // Renders `msg' along with the line of `src' the span starts on,
+    // underlining the span with carets.
+    pub fn render(&self, src: &str, msg: &str) -> String {
+        let start = self.start.min(src.len());
+        let line_start = src[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = src[start..].find('\n').map_or(src.len(), |i| start + i);
+        let line = &src[line_start..line_end];
+        let (lineno, col) = self.line_col(src);
+        let col = col - 1;
+        let end = self.end.max(start).min(line_end);
+        let width = src[start..end].chars().count().max(1);
+        let gutter = " ".repeat(lineno.to_string().len());
+        format!("error: {}\n{}--> {}:{}\n{} |\n{} | {}\n{} | {}{}\n",
+                msg, gutter, lineno, col + 1, gutter, lineno, line,
+                gutter, " ".repeat(col), "^".repeat(width))
+    }
+}
+
+// Characters are written #\a, or #\space etc. for named characters, or #\x41
+// by code point.
+const CHAR_NAMES: &'static [(&'static str, char)] = &[
+    ("nul", '\0'), ("tab", '\t'), ("newline", '\n'), ("return", '\r'),
+    ("space", ' '), ("delete", '\x7f'),
+];
+
+pub fn fmt_char(c: char, f: &mut Formatter) -> Result<(), fmt::Error> {
+    if let Some(&(name, _)) = CHAR_NAMES.iter().find(|e| e.1 == c) {
+        write!(f, "#\\{}", name)
+    } else if c.is_control() || c.is_whitespace() {
+        write!(f, "#\\x{:x}", c as u32)
+    } else {
+        write!(f, "#\\{}", c)
+    }
+}
+
+fn parse_char(name: &str) -> Option<char> {
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => return Some(c),
+        (Some('x'), Some(_)) => {
+            return u32::from_str_radix(&name[1..], 16).ok()
+                .and_then(::std::char::from_u32)
+        }
+        _ => {}
+    }
+    CHAR_NAMES.iter().find(|e| e.0 == name).map(|e| e.1)
+}
+
 // Parsing s-expressions.
 impl FromStr for Sexp {
     type Err = ParseError;
     fn from_str(s: &str) -> Result<Sexp, ParseError> {
-        let mut syms = HashMap::new();
-        parse(&mut syms, s).and_then(|e| {
+        let re = regexes();
+        parse_sexp(&re, s, skip_ws(&re, s, 0)).and_then(|e| {
             let (sexp, i) = e;
-            if s[i..].trim_left().is_empty() {
+            if skip_ws(&re, s, i) == s.len() {
                 Ok(sexp)
             } else {
                 Err(ParseError::Other(
@@ -48,9 +130,16 @@ impl FromStr for Sexp {
     }
 }
 
-// we can't get keys out of hashsets by looking them up, so we have to use an
-// identity hashmap for now.
-type Symtab = HashMap<Str, Str>;
+impl Sexp {
+    // Reads all the sexps in `s', for files holding more than one.
+    pub fn parse_all(s: &str) -> Result<Vec<Sexp>, ParseError> {
+        let re = regexes();
+        parse_sexps(&re, s, 0).and_then(|e| {
+            let (sexps, i) = e;
+            if i == s.len() { Ok(sexps) } else { Err(ParseError::RightParen) }
+        })
+    }
+}
 
 type ParseResult<A> = Result<(A, usize), ParseError>;
 #[derive(Debug)]
@@ -59,78 +148,125 @@ pub enum ParseError {
     RightParen,
     Other(String),
 }
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            ParseError::EOF => f.write_str("unexpected end of input"),
+            ParseError::RightParen => f.write_str("unexpected )"),
+            ParseError::Other(ref s) => f.write_str(s),
+        }
+    }
+}
+
 fn eof<A>() -> ParseResult<A> { Err(ParseError::EOF) }
 fn err<A, S: ToString>(s: S) -> ParseResult<A> {
     Err(ParseError::Other(s.to_string()))
 }
 
 // regexes needed for parsing.
-struct Regexes { ws: Regex, symbol: Regex, string: Regex, number: Regex }
+struct Regexes {
+    ws: Regex, string: Regex, chr: Regex,
+    // an atom is any run of non-delimiters; we then decide what kind it is.
+    atom: Regex, symbol: Regex, int: Regex, ratio: Regex, float: Regex,
+}
 
 // first index after i that isn't whitespace
 fn skip_ws(re: &Regexes, input: &str, i: usize) -> usize {
     i + re.ws.find(&input[i..]).unwrap_or((0,0)).1
 }
 
-fn parse(syms: &mut Symtab, input: &str) -> ParseResult<Sexp> {
-    let re = Regexes {
-        ws: Regex::new(r"^\s+").unwrap(),
-        symbol: Regex::new(r"^[a-zA-Z][a-zA-Z0-9_-]*").unwrap(),
+fn regexes() -> Regexes {
+    Regexes {
+        // comments run from ; to the end of the line.
+        ws: Regex::new(r"^(\s|;[^\n]*)+").unwrap(),
         // TODO: string escapes.
         string: Regex::new("^\"[^\"]*\"").unwrap(),
-        number: Regex::new(r"^\d+").unwrap(),
-    };
-    parse_sexp(&re, syms, input, skip_ws(&re, input, 0))
+        chr: Regex::new(r#"^#\\([^\s()"]+|.)"#).unwrap(),
+        atom: Regex::new(r#"^[^\s()"';]+"#).unwrap(),
+        symbol: Regex::new(
+            r"^[a-zA-Z!$%&*/:<=>?^_~][a-zA-Z0-9!$%&*/:<=>?^_~+.-]*$").unwrap(),
+        int: Regex::new(r"^[+-]?\d+$").unwrap(),
+        ratio: Regex::new(r"^[+-]?\d+/\d+$").unwrap(),
+        float: Regex::new(
+            r"^[+-]?(\d+(\.\d*)?([eE][+-]?\d+)?|inf|nan)$").unwrap(),
+    }
 }
 
-fn parse_sexp(re: &Regexes, syms: &mut Symtab, input: &str, i: usize)
-              -> ParseResult<Sexp>
-{
+fn parse_sexp(re: &Regexes, input: &str, i: usize) -> ParseResult<Sexp> {
     let rest = &input[i..];
     if rest.is_empty() {
         eof()
-    } else if &rest[0..1] == "(" {
-        parse_sexps(re, syms, input, i+1).and_then(|e| {
+    } else if rest.starts_with('(') {
+        parse_sexps(re, input, i+1).and_then(|e| {
             let (v,j) = e;
             if j == input.len() {
                 eof()
-            } else if ")" == &input[j..j+1] {
-                Ok((Sexp::List(v), j+1))
+            } else if input[j..].starts_with(')') {
+                Ok((Sexp::new(SexpKind::List(v), Span { start: i, end: j+1 }),
+                    j+1))
             } else {
                 err("unknown")
             }
         })
-    } else if &rest[0..1] == ")" {
+    } else if rest.starts_with(')') {
         Err(ParseError::RightParen)
-    } else if let Some((_,j)) = re.symbol.find(rest) {
-        match syms.get(&rest[0..j]) {
-            Some(s) => return Ok((Sexp::Symbol(s.clone()), i+j)),
-            None => {}
-        }
-        let s = Str::new(&rest[0..j]);
-        let (sk,sv) = (s.clone(), s.clone());
-        syms.insert(sk, sv);
-        Ok((Sexp::Symbol(s), i+j))
+    } else if rest.starts_with('\'') {
+        // 'x is short for (quote x)
+        let quote = Sexp::new(SexpKind::Symbol(Symbol::intern("quote")),
+                              Span { start: i, end: i+1 });
+        parse_sexp(re, input, skip_ws(re, input, i+1)).map(|e| {
+            let (e, j) = e;
+            (Sexp::new(SexpKind::List(vec![quote, e]), Span { start: i, end: j }),
+             j)
+        })
     } else if let Some((_,j)) = re.string.find(rest) {
         // FIXME: NEED TO DEAL WITH ESCAPES
-        Ok((Sexp::String(Str::new(&rest[1..j-1])), i+j))
-    } else if let Some((_,j)) = re.number.find(rest) {
-        match rest[0..j].parse::<i64>() {
-            Ok(n) => Ok((Sexp::Int(n), i+j)),
-            Err(e) => err(format!("{}", e))
+        let s = SexpKind::String(Str::new(&rest[1..j-1]));
+        Ok((Sexp::new(s, Span { start: i, end: i+j }), i+j))
+    } else if let Some((_,j)) = re.chr.find(rest) {
+        match parse_char(&rest[2..j]) {
+            Some(c) => Ok((Sexp::new(SexpKind::Char(c),
+                                     Span { start: i, end: i+j }), i+j)),
+            None => err(format!("unknown character: {}", &rest[0..j])),
         }
+    } else if let Some((_,j)) = re.atom.find(rest) {
+        parse_atom(re, &rest[0..j])
+            .map(|e| (Sexp::new(e, Span { start: i, end: i+j }), i+j))
     } else {
         err(String::from("could not parse"))
     }
 }
 
-fn parse_sexps(re: &Regexes, syms: &mut Symtab, input: &str, mut i: usize)
+fn parse_atom(re: &Regexes, atom: &str) -> Result<SexpKind, ParseError> {
+    if re.int.is_match(atom) {
+        // Too big for a fixnum means a bignum.
+        Ok(atom.parse::<i64>().map(SexpKind::Int).unwrap_or_else(|_| {
+            SexpKind::Big(atom.parse().unwrap())}))
+    } else if re.ratio.is_match(atom) {
+        let slash = atom.find('/').unwrap();
+        let (n, d): (BigInt, BigInt) = (atom[..slash].parse().unwrap(),
+                                        atom[slash+1..].parse().unwrap());
+        if d.is_zero() {
+            return Err(ParseError::Other(format!("zero denominator: {}", atom)))
+        }
+        Ok(SexpKind::Ratio(n, d))
+    } else if re.float.is_match(atom) {
+        atom.parse::<f64>().map(SexpKind::Float)
+            .map_err(|e| ParseError::Other(format!("{}", e)))
+    } else if re.symbol.is_match(atom) {
+        Ok(SexpKind::Symbol(Symbol::intern(atom)))
+    } else {
+        Err(ParseError::Other(format!("invalid token: {}", atom)))
+    }
+}
+
+fn parse_sexps(re: &Regexes, input: &str, mut i: usize)
                -> ParseResult<Vec<Sexp>>
 {
     let mut v = vec![];
     loop {
         i = skip_ws(re, input, i);
-        match parse_sexp(re, syms, input, i) {
+        match parse_sexp(re, input, i) {
             Ok((e, j)) => { v.push(e); i = j; }
             Err(ParseError::RightParen) | Err(ParseError::EOF)
                 => return Ok((v, i)),
@@ -1,6 +1,11 @@
 use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::hash::{Hash,Hasher};
 use std::ops::Deref;
 use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::fmt;
 use std::fmt::{Display,Formatter,Error};
 
 // Ugh. I hate having to do this, but.
@@ -28,3 +33,67 @@ impl Display for Str {
         s.fmt(f)
     }
 }
+
+// ---------- Interned strings ----------
+// There's one Symbol per distinct name, so symbols compare and hash by
+// pointer. Rc isn't Send, so the intern table is per-thread; symbols are never
+// freed.
+#[derive(Clone)]
+pub struct Symbol(Str);
+
+thread_local!(static SYMBOLS: RefCell<HashSet<Str>> = RefCell::new(HashSet::new()));
+
+impl Symbol {
+    pub fn intern(name: &str) -> Symbol {
+        SYMBOLS.with(|table| {
+            let mut table = table.borrow_mut();
+            if let Some(s) = table.get(name) { return Symbol(s.clone()) }
+            let s = Str::new(name);
+            table.insert(s.clone());
+            Symbol(s)
+        })
+    }
+
+    pub fn to_str(&self) -> Str { self.0.clone() }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Symbol) -> bool { Rc::ptr_eq(&(self.0).0, &(other.0).0) }
+}
+impl Eq for Symbol {}
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool { &**self == other }
+}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (&*(self.0).0 as *const String).hash(state)
+    }
+}
+
+// Alphabetical, not by address, so that it's deterministic.
+impl Ord for Symbol {
+    fn cmp(&self, other: &Symbol) -> Ordering {
+        if self == other { Ordering::Equal } else { self.0.cmp(&other.0) }
+    }
+}
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Symbol) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+    fn deref(&self) -> &str { &*self.0 }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut Formatter) -> Result<(),Error> { self.0.fmt(f) }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut Formatter) -> Result<(),Error> {
+        write!(f, "Symbol({:?})", &**self)
+    }
+}
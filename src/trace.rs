@@ -0,0 +1,74 @@
+// Watching programs run. A VM with a tracer tells it about each instruction,
+// call, return and prim it runs; without one, it only has to check that it
+// hasn't got one.
+use std::io::Write;
+
+use cam::*;
+use disasm::show_instr;
+use lang::*;
+
+// Each method is called just before the VM does what it describes, except
+// prim, which is called once the prim has succeeded. They do nothing unless
+// overridden.
+pub trait Tracer {
+    // The VM is at the instruction it's about to run; see VM::location.
+    fn instr(&mut self, _vm: &VM) {}
+    fn call(&mut self, _vm: &VM, _proto: &Proto, _args: &[Val], _tail: bool) {}
+    // Returning `val' from the current frame.
+    fn ret(&mut self, _vm: &VM, _val: &Val) {}
+    fn prim(&mut self, _vm: &VM, _prim: Prim, _args: &[Val], _val: &Val) {}
+}
+
+// Writes a line per event to `out', indented by the number of calls in
+// progress. Errors writing are ignored, so as not to disturb the program.
+pub struct Trace<W> {
+    out: W,
+    instrs: bool,
+}
+
+impl<W: Write> Trace<W> {
+    // Every instruction, with the stack before it, as well as calls.
+    pub fn all(out: W) -> Trace<W> { Trace { out: out, instrs: true } }
+
+    // Just calls, returns and prims.
+    pub fn calls(out: W) -> Trace<W> { Trace { out: out, instrs: false } }
+
+    pub fn into_inner(self) -> W { self.out }
+
+    fn line(&mut self, vm: &VM, text: String) {
+        let _ = writeln!(self.out, "{:1$}{2}", "", 2 * vm.depth(), text);
+    }
+}
+
+impl<W: Write> Tracer for Trace<W> {
+    fn instr(&mut self, vm: &VM) {
+        if !self.instrs { return }
+        let text = {
+            let (proto, offset) = vm.location();
+            let instr = proto.decode(&mut offset.clone());
+            format!("{:>4}  {:<20} {}", offset, show_instr(&instr),
+                    vals(vm.stack()))
+        };
+        self.line(vm, text)
+    }
+
+    fn call(&mut self, vm: &VM, proto: &Proto, args: &[Val], tail: bool) {
+        let name = proto.debug.as_ref().and_then(|d| d.name.as_ref())
+            .map_or("<fn>".to_string(), |n| n.to_string());
+        let call = if tail { "tail-call" } else { "call" };
+        self.line(vm, format!("{} {} {}", call, name, vals(args)))
+    }
+
+    fn ret(&mut self, vm: &VM, val: &Val) {
+        self.line(vm, format!("return {}", val))
+    }
+
+    fn prim(&mut self, vm: &VM, prim: Prim, args: &[Val], val: &Val) {
+        self.line(vm, format!("{} {} => {}", prim, vals(args), val))
+    }
+}
+
+fn vals(vals: &[Val]) -> String {
+    let vals: Vec<String> = vals.iter().map(|v| v.to_string()).collect();
+    format!("[{}]", vals.join(" "))
+}
@@ -0,0 +1,166 @@
+// Checks that code can run without the VM tripping over it: no instruction
+// pops more than is on the stack or refers to a variable that isn't in its
+// env, every path to an instruction reaches it with the same stack depth and
+// env size, jumps stay in the code, and functions return exactly one value.
+//
+// A proto's env on entry is that of the closure instruction that made it,
+// plus its parameters; so protos are checked from the closures that use them.
+use std::collections::HashSet;
+use std::fmt;
+use std::rc::Rc;
+
+use cam::*;
+use lang::VarIndex;
+
+#[derive(Clone,PartialEq,Debug)]
+pub enum VerifyError {
+    // An instruction needed more values than were on the stack.
+    Underflow { at: usize, depth: usize },
+    // Two paths reached an instruction with different stack depths.
+    Mismatch { at: usize, depth: usize, other: usize },
+    // A function returned with other than one value on its stack. For a
+    // tail call, `depth' counts the function and its arguments as the one
+    // value their result will be.
+    BadReturn { at: usize, depth: usize },
+    BadTarget { at: usize, target: usize },
+    // An instruction referred to (or unbound) variables beyond its env.
+    BadVar { at: usize, index: VarIndex, env: usize },
+    // Two paths reached an instruction with different numbers of variables.
+    EnvMismatch { at: usize, env: usize, other: usize },
+    // Control ran off the end of the code without returning.
+    FallsOffEnd,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(),fmt::Error> {
+        match *self {
+            VerifyError::Underflow { at, depth } =>
+                write!(f, "instruction {}: stack underflow at depth {}", at, depth),
+            VerifyError::Mismatch { at, depth, other } =>
+                write!(f, "instruction {}: reached with stack depths {} and {}",
+                       at, depth, other),
+            VerifyError::BadReturn { at, depth } =>
+                write!(f, "instruction {}: return at stack depth {}", at, depth),
+            VerifyError::BadTarget { at, target } =>
+                write!(f, "instruction {}: jump to {} is out of bounds",
+                       at, target),
+            VerifyError::BadVar { at, index, env } =>
+                write!(f, "instruction {}: variable {} is outside an env of {}",
+                       at, index, env),
+            VerifyError::EnvMismatch { at, env, other } =>
+                write!(f, "instruction {}: reached with env sizes {} and {}",
+                       at, env, other),
+            VerifyError::FallsOffEnd => f.write_str("code falls off its end"),
+        }
+    }
+}
+
+pub type VerifyResult = Result<(), VerifyError>;
+
+// Verifies `proto', which is run with an empty env, and every proto it makes
+// closures of. The proto must decode; see Proto::check.
+pub fn verify(proto: &Proto) -> VerifyResult {
+    verify_in(proto, 0)
+}
+
+// Verifies `proto' to be run with `env' variables already bound.
+pub fn verify_in(proto: &Proto, env: usize) -> VerifyResult {
+    Verifier { seen: HashSet::new() }.code(&proto.instrs(), env)
+}
+
+pub fn verify_code(code: &Code) -> VerifyResult {
+    Verifier { seen: HashSet::new() }.code(code, 0)
+}
+
+struct Verifier {
+    // The protos already verified, and the env sizes they were verified with.
+    // A proto can be closed over from more than one place.
+    seen: HashSet<(*const Proto, usize)>,
+}
+
+impl Verifier {
+    fn code(&mut self, code: &Code, env: usize) -> VerifyResult {
+        let closures = try!(verify_stack(code, env));
+        for (proto, env) in closures {
+            let env = env + proto.arity as usize;
+            if self.seen.insert((&*proto as *const Proto, env)) {
+                try!(self.code(&proto.instrs(), env))
+            }
+        }
+        Ok(())
+    }
+}
+
+// Checks `code' run with `env' variables, returning the protos it makes
+// closures of and the env size each closes over.
+fn verify_stack(code: &Code, env: usize)
+                -> Result<Vec<(Rc<Proto>, usize)>, VerifyError>
+{
+    let mut closures = vec![];
+    let mut states: Vec<Option<(usize, usize)>> = vec![None; code.len()];
+    let mut todo = vec![(0, 0, env)];
+    while let Some((i, depth, env)) = todo.pop() {
+        if i >= code.len() { return Err(VerifyError::FallsOffEnd) }
+        match states[i] {
+            Some((other, _)) if other != depth =>
+                return Err(VerifyError::Mismatch {
+                    at: i, depth: depth, other: other }),
+            Some((_, other)) if other != env =>
+                return Err(VerifyError::EnvMismatch {
+                    at: i, env: env, other: other }),
+            Some(_) => continue,
+            None => states[i] = Some((depth, env)),
+        }
+        let (pops, pushes) = effect(&code[i]);
+        if depth < pops {
+            return Err(VerifyError::Underflow { at: i, depth: depth })
+        }
+        let after = depth - pops + pushes;
+        let bad_var = |index| VerifyError::BadVar { at: i, index: index, env: env };
+        let target = |t: InstrIndex| {
+            if (t as usize) < code.len() { Ok((t as usize, after, env)) }
+            else { Err(VerifyError::BadTarget { at: i, target: t as usize }) }
+        };
+        match code[i] {
+            Instr::Get(index) | Instr::Set(index) | Instr::BoxVar(index)
+            | Instr::GetBox(index) | Instr::SetBox(index)
+                if index as usize >= env => return Err(bad_var(index)),
+            Instr::Return if depth != 1 =>
+                return Err(VerifyError::BadReturn { at: i, depth: depth }),
+            // Tail calls return what they call, but leave what was below
+            // the function on the caller's stack.
+            Instr::TailApply(n) if depth != n as usize + 1 =>
+                return Err(VerifyError::BadReturn {
+                    at: i, depth: depth - n as usize }),
+            Instr::Return | Instr::TailApply(_) => {}
+            Instr::Jump(t) => todo.push(try!(target(t))),
+            Instr::If(thn, els) => {
+                todo.push(try!(target(thn)));
+                todo.push(try!(target(els)));
+            }
+            Instr::Bind(n) => todo.push((i + 1, after, env + n as usize)),
+            Instr::Unbind(n) if n as usize > env => return Err(bad_var(n)),
+            Instr::Unbind(n) => todo.push((i + 1, after, env - n as usize)),
+            Instr::Closure(ref proto) => {
+                closures.push((proto.clone(), env));
+                todo.push((i + 1, after, env))
+            }
+            _ => todo.push((i + 1, after, env)),
+        }
+    }
+    Ok(closures)
+}
+
+// How many values an instruction pops, and how many it pushes.
+fn effect(instr: &Instr) -> (usize, usize) {
+    use cam::Instr::*;
+    match *instr {
+        Get(_) | GetBox(_) | Push(_) | Closure(_) => (0, 1),
+        Set(_) | SetBox(_) | Pop | If(..) => (1, 0),
+        BoxVar(_) | Unbind(_) | Jump(_) | Return => (0, 0),
+        Bind(n) => (n as usize, 0),
+        Apply(n) => (n as usize + 1, 1),
+        TailApply(n) => (n as usize + 1, 0),
+        Prim(p) => (p.arity() as usize, 1),
+    }
+}
@@ -0,0 +1,95 @@
+extern crate cam;
+
+use std::fs;
+use std::io::Read;
+use std::str::FromStr;
+
+use cam::cam::{VM,Instr,Proto};
+use cam::compile::compile;
+use cam::disasm::disassemble;
+use cam::lang::*;
+use cam::parse::ParseFrom;
+use cam::sexp::Sexp;
+
+fn build(src: &str) -> Proto {
+    compile(&Exp::parse_from(&Sexp::from_str(src).unwrap()).unwrap())
+}
+
+fn assemble(src: &str) -> Result<Proto, SyntaxError> {
+    Proto::parse_from(&Sexp::parse_all(src).unwrap()[..])
+}
+
+fn run(src: &str) -> String {
+    VM::run(assemble(src).unwrap()).unwrap().to_string()
+}
+
+#[test]
+fn round_trips_with_disassembler() {
+    let literals = build(
+        "(cons nil (cons true (cons -12 (cons 123456789012345678901234567890
+           (cons -3/4 (cons 2.5 (cons #\\x3bb (cons \"str\" (cons add
+             (cons 'sym (cons nan nil)))))))))))");
+    let mut protos = vec![literals];
+    for entry in fs::read_dir("tests/programs").unwrap() {
+        let mut src = String::new();
+        fs::File::open(entry.unwrap().path()).unwrap()
+            .read_to_string(&mut src).unwrap();
+        protos.push(build(&src));
+    }
+    for proto in protos {
+        let text = disassemble(&proto);
+        let loaded = assemble(&text).unwrap();
+        assert_eq!(format!("{:?}", proto), format!("{:?}", loaded), "{}", text);
+        assert_eq!(VM::run(proto).unwrap().to_string(),
+                   VM::run(loaded).unwrap().to_string());
+    }
+}
+
+#[test]
+fn runs_hand_written_code() {
+    assert_eq!(run("(proto (arity 0) (push 1) (push 2) (prim add) (return))"),
+               "3");
+    // Labels may be used before they're defined; closures can be written in
+    // place or named.
+    assert_eq!(run("
+        (proto main (arity 0)
+            (push false)
+            (if yes no)
+        yes: (push 'wrong) (return)
+        no:  (closure double)
+             (closure (proto (arity 0) (push 21) (return)))
+             (apply 0)
+             (tail-apply 1))
+        (proto double (arity 1)
+            (get 0) (get 0) (prim add) (return))"), "42");
+}
+
+#[test]
+fn parses_single_instrs() {
+    let instr = |src| Instr::parse_from(&Sexp::from_str(src).unwrap());
+    assert_eq!(format!("{:?}", instr("(jump 3)")), "Ok(Jump(3))");
+    assert_eq!(format!("{:?}", instr("(push 'x)")),
+               "Ok(Push(Symbol(Symbol(\"x\"))))");
+    assert_eq!(instr("(jump L3)").unwrap_err().to_string(),
+               "undefined label `L3`");
+}
+
+#[test]
+fn rejects_bad_code() {
+    let err = |src| assemble(src).unwrap_err().to_string();
+    assert_eq!(err("(proto (arity 0) (frob 1))"),
+               "unrecognized instruction `frob`");
+    assert_eq!(err("(proto (arity 0) (get))"), "get takes 1 argument, got 0");
+    assert_eq!(err("(proto (arity 0) (get -1))"), "invalid operand");
+    assert_eq!(err("(proto (arity 0) (jump 99))"), "invalid operand");
+    assert_eq!(err("(proto (arity 0) (push true) (if 0 4) (return))"),
+               "invalid operand");
+    assert_eq!(err("(proto (arity 0) a: a: (return))"),
+               "label `a` is defined twice");
+    assert_eq!(err("(proto (arity 0) (closure p9))"), "undefined proto `p9`");
+    assert_eq!(err("(proto p0 (arity 0) (closure p1) (return))
+                    (proto p1 (arity 0) (closure p0) (return))"),
+               "proto `p0` contains itself");
+    assert_eq!(err("(proto (push 1))"),
+               "expected (proto [id] (arity n) instruction...)");
+}
@@ -0,0 +1,67 @@
+extern crate cam;
+
+use std::str::FromStr;
+
+use cam::cam::{VM,Proto};
+use cam::compile::compile;
+use cam::lang::*;
+use cam::parse::ParseFrom;
+use cam::peephole;
+use cam::sexp::Sexp;
+
+fn build(src: &str) -> Proto {
+    compile(&Exp::parse_from(&Sexp::from_str(src).unwrap()).unwrap())
+}
+
+// Runs `proto' to its error, and renders the backtrace.
+fn trace(proto: Proto, src: &str) -> String {
+    let mut vm = VM::new(proto).unwrap();
+    vm.finish().unwrap_err();
+    vm.backtrace().iter().map(|f| f.render(src)).collect()
+}
+
+const SRC: &'static str = "\
+(let ((inner (fn (x) (car x))))
+  (let ((middle (fn (x) (add 1 (inner x)))))
+    (let ((outer (fn (x) (middle x))))
+      (cons 1 (outer 5)))))";
+
+#[test]
+fn names_frames_and_positions() {
+    assert_eq!(trace(build(SRC), SRC), "  in inner at 1:22
+  in middle at 2:32
+  ... 1 frame elided by tail calls
+  in <top> at 4:15
+");
+}
+
+#[test]
+fn survives_peephole() {
+    assert_eq!(trace(peephole::optimize(&build(SRC)), SRC),
+               trace(build(SRC), SRC));
+}
+
+#[test]
+fn counts_elided_frames() {
+    let src = "(let ((loop (fn (self n) (if (eq n 0) (car n)
+                                      (self self (sub n 1))))))
+                 (loop loop 3))";
+    // The top level's call is a tail call too.
+    assert_eq!(trace(build(src), src), "  in loop at 1:39
+  ... 4 frames elided by tail calls
+");
+}
+
+#[test]
+fn set_names_functions() {
+    let src = "(let ((f nil)) (let ((u (set! f (fn () (car 1))))) (f)))";
+    assert!(trace(build(src), src).starts_with("  in f at"));
+}
+
+#[test]
+fn frames_without_debug_info() {
+    // Hand-written code has no names or positions.
+    let proto = Proto::parse_from(&Sexp::from_str(
+        "(proto (arity 0) (push 1) (prim car) (return))").unwrap()).unwrap();
+    assert_eq!(trace(proto, ""), "  in <fn>\n");
+}
@@ -0,0 +1,139 @@
+extern crate cam;
+
+use cam::bigint::BigInt;
+use cam::lang::Lit;
+use cam::num;
+
+fn big(s: &str) -> BigInt { s.parse().unwrap() }
+
+fn div_rem(u: &str, v: &str) -> (String, String) {
+    let (q, r) = big(u).div_rem(&big(v));
+    (q.to_string(), r.to_string())
+}
+
+#[test]
+fn parses_and_prints() {
+    for s in &["0", "1", "-1", "999999999", "1000000000", "4294967296",
+               "-18446744073709551616",
+               "123456789012345678901234567890123456789"] {
+        assert_eq!(big(s).to_string(), *s);
+    }
+    assert_eq!(big("+00012").to_string(), "12");
+    assert_eq!(big("-0").to_string(), "0");
+    assert!(!big("-0").is_negative());
+    assert!("".parse::<BigInt>().is_err());
+    assert!("1x".parse::<BigInt>().is_err());
+}
+
+#[test]
+fn carries_and_borrows_across_digits() {
+    assert_eq!(big("4294967295").add(&big("1")).to_string(), "4294967296");
+    assert_eq!(big("18446744073709551615").add(&big("1")).to_string(),
+               "18446744073709551616");
+    assert_eq!(big("18446744073709551616").sub(&big("1")).to_string(),
+               "18446744073709551615");
+    assert_eq!(big("1").sub(&big("18446744073709551616")).to_string(),
+               "-18446744073709551615");
+    assert_eq!(big("-5").add(&big("5")), BigInt::zero());
+    assert_eq!(big("18446744073709551615").mul(&big("18446744073709551615"))
+                   .to_string(),
+               "340282366920938463426481119284349108225");
+    assert_eq!(big("-3").mul(&big("4294967296")).to_string(), "-12884901888");
+}
+
+#[test]
+fn divides_single_digit_divisors() {
+    assert_eq!(div_rem("18446744073709551617", "7"),
+               ("2635249153387078802".to_string(), "3".to_string()));
+    // Truncating: the remainder has the dividend's sign.
+    assert_eq!(div_rem("-7", "2"), ("-3".to_string(), "-1".to_string()));
+    assert_eq!(div_rem("7", "-2"), ("-3".to_string(), "1".to_string()));
+    assert_eq!(div_rem("3", "18446744073709551616"),
+               ("0".to_string(), "3".to_string()));
+}
+
+#[test]
+fn divides_multi_digit_divisors() {
+    // Each of these needs algorithm D's rarer steps: the first two guess a
+    // quotient digit one too big and have to add the divisor back; the
+    // third corrects its guess before multiplying.
+    let cases = [
+        ("170141183420855150474555134919112130560",
+         "39614081257132168796771975169",
+         "4294967294", "39614081257132168792477007874"),
+        ("39614081257132168796771975171",
+         "9903520314283042199192993793",
+         "3", "9903520314283042199192993792"),
+        ("39614081275578912861891592192", "9223372041149743103",
+         "4294967295", "9223372036854775807"),
+        ("515377520732011331036461129765621272702107522001",
+         "6366805760909027985741435139224001",
+         "80947580322982", "3257168497772627735109697681231019"),
+    ];
+    for &(u, v, q, r) in &cases {
+        assert_eq!(div_rem(u, v), (q.to_string(), r.to_string()), "{} / {}",
+                   u, v);
+        // And the signs work out as for small numbers.
+        let neg = format!("-{}", u);
+        assert_eq!(div_rem(&neg, v), (format!("-{}", q), format!("-{}", r)));
+    }
+}
+
+#[test]
+fn converts_to_and_from_i64() {
+    let min = BigInt::from_i64(i64::min_value());
+    assert_eq!(min.to_string(), "-9223372036854775808");
+    assert_eq!(min.to_i64(), Some(i64::min_value()));
+    assert_eq!(min.neg().to_string(), "9223372036854775808");
+    assert_eq!(min.neg().to_i64(), None);
+    assert_eq!(min.sub(&BigInt::one()).to_i64(), None);
+    assert_eq!(BigInt::from_i64(i64::max_value()).to_i64(),
+               Some(i64::max_value()));
+    assert_eq!(big("-4294967296").to_i64(), Some(-4294967296));
+}
+
+#[test]
+fn converts_to_and_from_f64() {
+    assert_eq!(big("9007199254740993").to_f64(), 9007199254740992.0);
+    assert_eq!(big("9007199254740995").to_f64(), 9007199254740996.0);
+    assert_eq!(big("-1000000000000000000000000000000").to_f64(), -1e30);
+    // 2^96 + 2^43 + 1 is just over halfway between two floats, and must
+    // round up, which rounding a digit at a time wouldn't.
+    assert_eq!(big("79228162514264346389636972545").to_f64(),
+               79228162514264355185729994752.0);
+    assert_eq!(BigInt::from_f64(1e30).unwrap().to_string(),
+               "1000000000000000019884624838656");
+    assert_eq!(BigInt::from_f64(-3.0).unwrap().to_string(), "-3");
+    assert_eq!(BigInt::from_f64(0.5), None);
+    assert_eq!(BigInt::from_f64(::std::f64::INFINITY), None);
+}
+
+fn is_big(l: &Lit) -> bool {
+    match *l { Lit::Big(_) => true, _ => false }
+}
+
+#[test]
+fn fixnums_overflow_into_bignums_and_back() {
+    let (max, min) = (Lit::Int(i64::max_value()), Lit::Int(i64::min_value()));
+    let (one, two) = (Lit::Int(1), Lit::Int(2));
+    let over = num::add(&max, &one);
+    assert!(is_big(&over));
+    assert_eq!(over.to_string(), "9223372036854775808");
+    assert!(is_big(&num::sub(&min, &one)));
+    assert!(is_big(&num::mul(&min, &Lit::Int(-1))));
+    assert!(is_big(&num::div(&min, &Lit::Int(-1))));
+    assert!(is_big(&num::div(&num::mul(&over, &two), &two)));
+    // Results that fit are fixnums again.
+    match num::sub(&over, &one) {
+        Lit::Int(x) => assert_eq!(x, i64::max_value()),
+        l => panic!("expected a fixnum, got {:?}", l),
+    }
+    match num::div(&num::mul(&min, &two), &two) {
+        Lit::Int(x) => assert_eq!(x, i64::min_value()),
+        l => panic!("expected a fixnum, got {:?}", l),
+    }
+    match num::sub(&num::mul(&over, &over), &num::mul(&over, &over)) {
+        Lit::Int(0) => {}
+        l => panic!("expected a fixnum, got {:?}", l),
+    }
+}
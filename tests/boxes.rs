@@ -0,0 +1,79 @@
+extern crate cam;
+
+use std::str::FromStr;
+
+use cam::cam::{VM,Instr,Proto};
+use cam::compile::compile;
+use cam::lang::*;
+use cam::parse::ParseFrom;
+use cam::sexp::Sexp;
+
+fn build(src: &str) -> Proto {
+    compile(&Exp::parse_from(&Sexp::from_str(src).unwrap()).unwrap())
+}
+
+// Whether any instruction in `proto', or the protos of its closures,
+// satisfies `f'.
+fn any_instr<F: Fn(&Instr) -> bool>(proto: &Proto, f: &F) -> bool {
+    proto.instrs().iter().any(|instr| f(instr) || match *instr {
+        Instr::Closure(ref p) => any_instr(p, f),
+        _ => false,
+    })
+}
+
+fn boxes(proto: &Proto) -> bool {
+    any_instr(proto, &|instr| match *instr {
+        Instr::BoxVar(_) | Instr::GetBox(_) | Instr::SetBox(_) => true,
+        _ => false,
+    })
+}
+
+fn run(proto: Proto) -> String { VM::run(proto).unwrap().to_string() }
+
+#[test]
+fn closures_see_later_assignments() {
+    let proto = build("(let ((x 1))
+                         (let ((f (fn () x)))
+                           (let ((u (set! x 2)))
+                             (f))))");
+    assert!(boxes(&proto));
+    assert_eq!(run(proto), "2");
+}
+
+#[test]
+fn closures_share_boxes() {
+    let proto = build("(let ((n 0))
+                         (let ((inc (fn () (set! n (add n 1))))
+                               (get (fn () n)))
+                           (let ((a (inc)) (b (inc)))
+                             (cons (get) n))))");
+    assert!(boxes(&proto));
+    assert_eq!(run(proto), "(2 . 2)");
+}
+
+#[test]
+fn parameters_are_boxed_too() {
+    let proto = build("((fn (x)
+                          (let ((get (fn () x)))
+                            (let ((u (set! x (add x 1))))
+                              (get))))
+                        41)");
+    assert!(boxes(&proto));
+    assert_eq!(run(proto), "42");
+}
+
+#[test]
+fn only_captured_and_assigned_variables_are_boxed() {
+    // Assigned but not captured.
+    let proto = build("(let ((x 1)) (let ((u (set! x 2))) x))");
+    assert!(!boxes(&proto));
+    assert!(any_instr(&proto, &|i| match *i { Instr::Set(_) => true,
+                                              _ => false }));
+    assert_eq!(run(proto), "2");
+    // Captured but not assigned.
+    let proto = build("(let ((x 1)) (let ((f (fn () x))) (f)))");
+    assert!(!boxes(&proto));
+    assert_eq!(run(proto), "1");
+    // Assigned inside the closure that captures it.
+    assert!(boxes(&build("(let ((x 1)) (fn () (set! x 2)))")));
+}
@@ -0,0 +1,70 @@
+extern crate cam;
+
+use std::str::FromStr;
+
+use cam::cam::VM;
+use cam::compile::compile;
+use cam::lang::*;
+use cam::parse::ParseFrom;
+use cam::sexp::{Sexp,SexpKind};
+
+fn read_char(src: &str) -> char {
+    match Sexp::from_str(src).unwrap().kind {
+        SexpKind::Char(c) => c,
+        k => panic!("{}: expected a character, got {:?}", src, k),
+    }
+}
+
+fn eval(src: &str) -> String {
+    let e = Exp::parse_from(&Sexp::from_str(src).unwrap()).unwrap();
+    VM::run(compile(&e)).unwrap().to_string()
+}
+
+#[test]
+fn reads_characters() {
+    assert_eq!(read_char("#\\a"), 'a');
+    assert_eq!(read_char("#\\("), '(');
+    assert_eq!(read_char("#\\λ"), 'λ');
+    // A lone x is the letter, not an empty code point.
+    assert_eq!(read_char("#\\x"), 'x');
+    assert_eq!(read_char("#\\x41"), 'A');
+    assert_eq!(read_char("#\\x3bb"), 'λ');
+    assert_eq!(read_char("#\\x1F600"), '\u{1F600}');
+}
+
+#[test]
+fn reads_named_characters() {
+    for &(name, c) in &[("nul", '\0'), ("tab", '\t'), ("newline", '\n'),
+                        ("return", '\r'), ("space", ' '),
+                        ("delete", '\x7f')] {
+        assert_eq!(read_char(&format!("#\\{}", name)), c);
+    }
+}
+
+#[test]
+fn rejects_unknown_characters() {
+    for src in &["#\\bogus", "#\\xzz", "#\\xd800", "#\\x110000"] {
+        assert!(Sexp::from_str(src).is_err(), "{}", src);
+    }
+}
+
+#[test]
+fn prints_characters_so_they_read_back() {
+    for src in &["#\\a", "#\\λ", "#\\space", "#\\newline", "#\\nul",
+                 "#\\x1b", "#\\x3000"] {
+        let printed = Sexp::from_str(src).unwrap().to_string();
+        assert_eq!(printed, *src);
+        assert_eq!(eval(src), *src);
+    }
+    assert_eq!(eval("#\\x3bb"), "#\\λ");
+}
+
+#[test]
+fn character_prims() {
+    assert_eq!(eval("(char->integer #\\x3bb)"), "955");
+    assert_eq!(eval("(integer->char 955)"), "#\\λ");
+    assert_eq!(eval("(string-ref \"aλb\" 1)"), "#\\λ");
+    assert_eq!(eval("(string->list \"a b\")"), "(#\\a #\\space #\\b)");
+    assert_eq!(eval("(eq #\\a #\\a)"), "true");
+    assert_eq!(eval("(le #\\a #\\b)"), "true");
+}
@@ -0,0 +1,105 @@
+extern crate cam;
+
+use std::io::Cursor;
+use std::str::FromStr;
+
+use cam::cam::VM;
+use cam::compile::compile;
+use cam::debugger::Debugger;
+use cam::lang::*;
+use cam::parse::ParseFrom;
+use cam::sexp::Sexp;
+
+const SRC: &'static str = "\
+(let ((f (fn (x) (add x 1))))
+  (mul 2 (f 3)))";
+
+// Runs SRC under the debugger with `commands', returning what it printed,
+// without the prompts.
+fn session(commands: &str) -> String { session_in(SRC, commands) }
+
+fn session_in(src: &str, commands: &str) -> String {
+    let e = Exp::parse_from(&Sexp::from_str(src).unwrap()).unwrap();
+    let vm = VM::new(compile(&e)).unwrap();
+    let mut out = vec![];
+    Debugger::new(vm, src).run(Cursor::new(commands), &mut out).unwrap();
+    String::from_utf8(out).unwrap().replace("(debug) ", "")
+}
+
+#[test]
+fn breaks_on_functions() {
+    assert_eq!(session("break f\ncontinue\nenv\nframes\n"), "  in <top>  (closure f)
+breakpoint 1
+  in f  (get 0)
+  x = 3
+#0  in f
+#1  in <top> at 2:10
+");
+}
+
+#[test]
+fn breaks_on_lines() {
+    assert_eq!(session("break 2\ncontinue\ncontinue\nstack\n"), "  in <top>  (closure f)
+breakpoint 1
+  in <top> at 2:10  (apply 1)
+  in <top> at 2:3  (prim mul)
+  2
+  4
+");
+}
+
+#[test]
+fn steps_over_and_out_of_calls() {
+    let out = session("b 2\nc\nnext\nclear\nb f\nc\nout\nc\n");
+    assert!(out.contains("  in <top> at 2:10  (apply 1)
+  in <top> at 2:3  (prim mul)
+"), "{}", out);
+    // f was already called, so its breakpoint never stops us.
+    assert!(out.ends_with("breakpoint 1\nfinished: 8\n"), "{}", out);
+    let out = session("b f\nc\nout\nc\n");
+    assert!(out.ends_with("  in <top> at 2:3  (prim mul)\nfinished: 8\n"),
+            "{}", out);
+}
+
+#[test]
+fn evaluates_in_the_paused_frame() {
+    assert_eq!(session("b f\nc\nprint (add x 10)\np y\n"), "  in <top>  (closure f)
+breakpoint 1
+  in f  (get 0)
+13
+error: unbound variable `y`
+");
+}
+
+#[test]
+fn breaks_on_proto_ids() {
+    assert_eq!(session("break p1\ncontinue\n"), "  in <top>  (closure f)
+breakpoint 1
+  in f  (get 0)
+");
+    // Functions without names can only be stopped in by id.
+    let src = "(let ((g (fn (h) (h 1)))) (g (fn (y) (add y 2))))";
+    assert_eq!(session_in(src, "b p2\nc\nenv\nc\n"), "  in <top>  (closure g)
+breakpoint 1
+  in <fn>  (get 0)
+  g = <function>
+  y = 1
+finished: 3
+");
+    assert_eq!(session("break p2\n"), "  in <top>  (closure f)
+no proto p2
+");
+}
+
+#[test]
+fn warns_of_lines_without_calls() {
+    let src = "(let ((f (fn (x)\n           x)))\n  (f 1))";
+    let out = session_in(src, "b 2\nb 3\nb 9\nc\n");
+    assert!(out.contains("warning: no calls on line 2 to stop at
+breakpoint 1
+breakpoint 2
+warning: no calls on line 9 to stop at
+breakpoint 3
+  in <top> at 3:3  (tail-apply 1)
+"), "{}", out);
+}
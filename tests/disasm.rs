@@ -0,0 +1,57 @@
+extern crate cam;
+
+use std::str::FromStr;
+
+use cam::compile::compile;
+use cam::disasm::disassemble;
+use cam::lang::*;
+use cam::parse::ParseFrom;
+use cam::peephole;
+use cam::sexp::Sexp;
+
+fn disasm(src: &str) -> String {
+    let e = Exp::parse_from(&Sexp::from_str(src).unwrap()).unwrap();
+    disassemble(&compile(&e))
+}
+
+#[test]
+fn labels_jump_targets() {
+    assert_eq!(disasm("(if true 1 2)"), "\
+(proto p0 (arity 0)  ; <top>
+    0  (push true)
+    1  (if L2 L4)
+L2:
+    2  (push 1)
+    3  (return)
+L4:
+    4  (push 2)
+    5  (return))
+");
+}
+
+#[test]
+fn lists_nested_protos_with_names() {
+    assert_eq!(disasm("(let ((y 'a)) (fn (x) (cons x y)))"), "\
+(proto p0 (arity 0)  ; <top>
+    0  (push 'a)
+    1  (bind 1)            ; y
+    2  (closure p1)
+    3  (return))
+
+(proto p1 (arity 1)  ; x
+    0  (get 0)             ; x
+    1  (get 1)             ; y
+    2  (prim cons)
+    3  (return))
+");
+}
+
+#[test]
+fn names_survive_peephole() {
+    let e = Exp::parse_from(&Sexp::from_str(
+        "(fn (b) (let ((x (if b 1 2))) (let ((y x)) (add x y))))").unwrap())
+        .unwrap();
+    let text = disassemble(&peephole::optimize(&compile(&e)));
+    assert!(text.contains("(get 0)             ; y"), "{}", text);
+    assert!(text.contains("(get 1)             ; x"), "{}", text);
+}
@@ -0,0 +1,136 @@
+extern crate cam;
+
+use std::fs;
+use std::io::Read;
+use std::panic;
+use std::str::FromStr;
+
+use cam::cam::{VM,Proto};
+use cam::compile::compile;
+use cam::image::{self,LoadError};
+use cam::lang::*;
+use cam::parse::ParseFrom;
+use cam::sexp::Sexp;
+
+fn build(src: &str) -> Proto {
+    compile(&Exp::parse_from(&Sexp::from_str(src).unwrap()).unwrap())
+}
+
+fn programs() -> Vec<Proto> {
+    fs::read_dir("tests/programs").unwrap().map(|entry| {
+        let mut src = String::new();
+        fs::File::open(entry.unwrap().path()).unwrap()
+            .read_to_string(&mut src).unwrap();
+        build(&src)
+    }).collect()
+}
+
+// FNV-1a, as in the file format.
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c9dc5, |h, &b| (h ^ b as u32).wrapping_mul(0x01000193))
+}
+
+fn fix_checksum(bytes: &mut Vec<u8>) {
+    let sum = checksum(&bytes[10..]);
+    for i in 0..4 { bytes[6 + i] = (sum >> (8 * i)) as u8 }
+}
+
+#[test]
+fn round_trips() {
+    let literals = build(
+        "(cons nil (cons true (cons -12 (cons 123456789012345678901234567890
+           (cons -3/4 (cons 2.5 (cons #\\x3bb (cons \"str\" (cons add
+             (cons 'sym (cons nan nil)))))))))))");
+    for proto in programs().into_iter().chain(Some(literals)) {
+        let bytes = image::to_bytes(&proto);
+        let loaded = image::from_bytes(&bytes).unwrap();
+        assert_eq!(format!("{:?}", proto), format!("{:?}", loaded));
+        assert_eq!(image::to_bytes(&loaded), bytes);
+        assert_eq!(VM::run(proto).unwrap().to_string(),
+                   VM::run(loaded).unwrap().to_string());
+    }
+}
+
+#[test]
+fn rejects_bad_headers() {
+    let mut bytes = image::to_bytes(&build("(add 1 2)"));
+    match image::from_bytes(b"#!/bin/sh") {
+        Err(LoadError::NotBytecode) => {}
+        r => panic!("{:?}", r),
+    }
+    bytes[4] = 99;
+    match image::from_bytes(&bytes) {
+        Err(LoadError::Version(99)) => {}
+        r => panic!("{:?}", r),
+    }
+    bytes[4] = image::VERSION as u8;
+    let last = bytes.len() - 1;
+    bytes[last] ^= 1;
+    match image::from_bytes(&bytes) {
+        Err(LoadError::Checksum) => {}
+        r => panic!("{:?}", r),
+    }
+}
+
+#[test]
+fn rejects_truncated_files() {
+    for proto in programs() {
+        let bytes = image::to_bytes(&proto);
+        for len in 0..bytes.len() {
+            let mut short = bytes[..len].to_vec();
+            if len >= 10 { fix_checksum(&mut short) }
+            assert!(image::from_bytes(&short).is_err(), "length {}", len);
+        }
+    }
+}
+
+// Corrupting any byte of the body, even with a correct checksum, gives an
+// error or a valid proto; never a panic.
+#[test]
+fn survives_corruption() {
+    for proto in programs() {
+        let bytes = image::to_bytes(&proto);
+        for i in 10..bytes.len() {
+            for &x in &[0x01, 0x80, 0xff] {
+                let mut bad = bytes.clone();
+                bad[i] ^= x;
+                fix_checksum(&mut bad);
+                let r = panic::catch_unwind(|| { let _ = image::from_bytes(&bad); });
+                assert!(r.is_ok(), "panicked with byte {} ^ {:#x}", i, x);
+            }
+        }
+    }
+}
+
+// Files list the prims their code numbers, so code calls the prims it was
+// written to call, whatever order they're in now.
+#[test]
+fn looks_prims_up_by_name() {
+    let bytes = image::to_bytes(&build("(sub 5 (add 1 2))"));
+    // Where a name is in the list, after its length.
+    let at = |bytes: &[u8], name: &[u8]| {
+        let mut entry = vec![name.len() as u8];
+        entry.extend(name);
+        bytes.windows(entry.len()).position(|w| w == &entry[..]).unwrap() + 1
+    };
+    // The names are the same length, so they can swap places in the list.
+    let mut swapped = bytes.clone();
+    let (add, sub) = (at(&bytes, b"add"), at(&bytes, b"sub"));
+    swapped[add..add + 3].copy_from_slice(b"sub");
+    swapped[sub..sub + 3].copy_from_slice(b"add");
+    fix_checksum(&mut swapped);
+    let proto = image::from_bytes(&swapped).unwrap();
+    assert_eq!(VM::run(proto).unwrap().to_string(), "4");
+    // Prims we don't have are only a problem if they're used.
+    let mut unknown = bytes.clone();
+    let mul = at(&bytes, b"mul");
+    unknown[mul..mul + 3].copy_from_slice(b"mux");
+    fix_checksum(&mut unknown);
+    assert!(image::from_bytes(&unknown).is_ok());
+    unknown[add..add + 3].copy_from_slice(b"adx");
+    fix_checksum(&mut unknown);
+    match image::from_bytes(&unknown) {
+        Err(LoadError::Malformed("unknown prim")) => {}
+        r => panic!("{:?}", r),
+    }
+}
@@ -0,0 +1,59 @@
+extern crate cam;
+
+use std::str::FromStr;
+
+use cam::cam::{VM,Instr,Proto};
+use cam::compile::compile;
+use cam::disasm::disassemble;
+use cam::lang::*;
+use cam::parse::ParseFrom;
+use cam::peephole;
+use cam::sexp::Sexp;
+use cam::verify::verify;
+
+const BINDINGS: usize = 100000;
+
+// A function whose first jump has to cross a let of 100k bindings.
+fn source() -> String {
+    let mut binds = String::new();
+    for i in 0..BINDINGS { binds.push_str(&format!("(x{} {}) ", i, i)) }
+    format!("(let ((f (fn (b) (if b (let ({}) (add x1 x{})) -1))))
+               (cons (f true) (f false)))", binds, BINDINGS - 1)
+}
+
+fn build(src: &str) -> Proto {
+    compile(&Exp::parse_from(&Sexp::from_str(src).unwrap()).unwrap())
+}
+
+#[test]
+fn runs_functions_of_over_100k_instructions() {
+    let proto = build(&source());
+    let f = match proto.instrs()[0] {
+        Instr::Closure(ref f) => f.clone(),
+        ref instr => panic!("expected a closure, got {:?}", instr),
+    };
+    assert!(f.instrs().len() > BINDINGS);
+    assert!(f.code.len() > u16::max_value() as usize);
+    assert_eq!(verify(&proto), Ok(()));
+    let expected = format!("({} . -1)", BINDINGS);
+    assert_eq!(VM::run(proto).unwrap().to_string(), expected);
+    let optimized = peephole::optimize(&build(&source()));
+    assert_eq!(VM::run(optimized).unwrap().to_string(), expected);
+}
+
+#[test]
+fn wide_jumps_round_trip() {
+    let proto = build(&source());
+    let text = disassemble(&proto);
+    let loaded = Proto::parse_from(&Sexp::parse_all(&text).unwrap()[..])
+        .unwrap();
+    assert_eq!(loaded.check(), Ok(()));
+    assert_eq!(format!("{:?}", proto), format!("{:?}", loaded));
+}
+
+#[test]
+fn small_code_keeps_narrow_jumps() {
+    let proto = build("(if true 1 2)");
+    // push, if with two 16-bit targets, push, return, push, return
+    assert_eq!(proto.code.len(), 2 + 5 + 2 + 1 + 2 + 1);
+}
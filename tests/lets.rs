@@ -0,0 +1,103 @@
+extern crate cam;
+
+use std::str::FromStr;
+
+use cam::cam::{VM,Instr,Proto};
+use cam::compile::compile;
+use cam::lang::*;
+use cam::parse::ParseFrom;
+use cam::sexp::Sexp;
+
+fn build(src: &str) -> Proto {
+    compile(&Exp::parse_from(&Sexp::from_str(src).unwrap()).unwrap())
+}
+
+fn run(src: &str) -> String {
+    VM::run(build(src)).unwrap().to_string()
+}
+
+#[test]
+fn lets_bind_in_the_current_frame() {
+    let code = build("(add (let ((x 1)) x) 2)").instrs();
+    assert_eq!(format!("{:?}", code),
+               "[Push(Int(1)), Bind(1), Get(0), Unbind(1), Push(Int(2)), \
+                Prim(Add), Return]");
+}
+
+#[test]
+fn closures_outlive_their_lets() {
+    // f's closure captures x, which is unbound before f is called.
+    assert_eq!(run("(let ((f (let ((x 5)) (fn () x)))) (f))"), "5");
+    // Binding more variables after capturing mustn't disturb the closure,
+    // nor unbinding them the variables that are left.
+    assert_eq!(run("(let ((a 1))
+                      (let ((f (fn () a)))
+                        (cons (let ((b 2)) (cons (f) b))
+                              (cons (f) a))))"),
+               "((1 . 2) 1 . 1)");
+    assert_eq!(run("(let ((fs (let ((x 1))
+                                (let ((g (fn () x)))
+                                  (let ((y 2))
+                                    (cons g (fn () (add x y))))))))
+                      (cons ((car fs)) ((cdr fs))))"),
+               "(1 . 3)");
+}
+
+#[test]
+fn nested_lets() {
+    // In tail position, the frame goes when we return.
+    let proto = build("((fn (a) (let ((b 1)) (let ((c 2)) (add a (add b c)))))
+                        10)");
+    assert_eq!(VM::run(proto).unwrap().to_string(), "13");
+    // Elsewhere, each let unbinds its own variables.
+    assert_eq!(run("(add (let ((x 1)) (let ((y 2)) (add x y)))
+                         (let ((z 3)) (let ((w z)) (add z w))))"), "9");
+    // Shadowing, and bound expressions seeing only the outer scope.
+    assert_eq!(run("(let ((x 1)) (let ((x (add x 1)) (y x)) (cons x y)))"),
+               "(2 . 1)");
+    // Functions with lets, called from within lets.
+    assert_eq!(run("(let ((f (fn (n) (let ((m (mul n 2))) (add m 1)))))
+                      (let ((a (f 1)))
+                        (let ((b (f a)))
+                          (cons a b))))"),
+               "(3 . 7)");
+}
+
+#[test]
+fn lets_in_loops() {
+    // Each iteration binds n's closure afresh, in a frame the tail call then
+    // replaces; earlier closures must keep their own values.
+    assert_eq!(run("(let ((loop (fn (self n acc)
+                                  (if (eq n 0) acc
+                                      (let ((m (sub n 1)) (f (fn () n)))
+                                        (self self m (cons f acc)))))))
+                      (let ((fs (loop loop 3 nil)))
+                        (cons ((car fs)) ((car (cdr fs))))))"),
+               "(1 . 2)");
+    // Non-tail recursion, with lets either side of the call.
+    assert_eq!(run("(let ((sum (fn (self n)
+                                 (if (eq n 0) 0
+                                     (let ((m n))
+                                       (add (self self (sub m 1))
+                                            (let ((k m)) k)))))))
+                      (sum sum 100))"),
+               "5050");
+}
+
+#[test]
+fn unbind_leaves_the_right_variables() {
+    let binds = |src| -> Vec<String> {
+        build(src).instrs().iter().filter_map(|i| match *i {
+            Instr::Bind(n) => Some(format!("bind {}", n)),
+            Instr::Unbind(n) => Some(format!("unbind {}", n)),
+            _ => None,
+        }).collect()
+    };
+    // Lets in tail position leave their variables for the return to drop.
+    assert_eq!(binds("(let ((a 1) (b 2)) (let ((c 3)) (cons a c)))"),
+               ["bind 2", "bind 1"]);
+    assert_eq!(binds("(cons (let ((a 1) (b 2)) (let ((c 3)) (cons a c))) 0)"),
+               ["bind 2", "bind 1", "unbind 1", "unbind 2"]);
+    assert_eq!(run("(cons (let ((a 1) (b 2)) (let ((c 3)) (cons a c))) 0)"),
+               "((1 . 3) . 0)");
+}
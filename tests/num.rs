@@ -0,0 +1,61 @@
+extern crate cam;
+
+use std::str::FromStr;
+
+use cam::cam::VM;
+use cam::compile::compile;
+use cam::lang::*;
+use cam::parse::ParseFrom;
+use cam::sexp::Sexp;
+
+fn eval(src: &str) -> String {
+    let e = Exp::parse_from(&Sexp::from_str(src).unwrap()).unwrap();
+    VM::run(compile(&e)).unwrap().to_string()
+}
+
+#[test]
+fn mixed_arithmetic_is_inexact() {
+    assert_eq!(eval("(add 1 0.5)"), "1.5");
+    assert_eq!(eval("(mul 2 1.5)"), "3.0");
+    assert_eq!(eval("(div 1.0 4)"), "0.25");
+}
+
+#[test]
+fn compares_ints_and_floats_exactly() {
+    // 2^53 + 1 has no float; converting it to one would round it to 2^53.
+    assert_eq!(eval("(eq 9007199254740993 9007199254740992.0)"), "false");
+    assert_eq!(eval("(eq 9007199254740992.0 9007199254740993)"), "false");
+    assert_eq!(eval("(eq 9007199254740992 9007199254740992.0)"), "true");
+    assert_eq!(eval("(le 9007199254740993 9007199254740992.0)"), "false");
+    assert_eq!(eval("(le 9007199254740992.0 9007199254740993)"), "true");
+    assert_eq!(eval("(eq 1 1.0)"), "true");
+    assert_eq!(eval("(le -1 -1.5)"), "false");
+}
+
+#[test]
+fn compares_bignums_and_ratios_with_floats_exactly() {
+    // The float nearest 10^23 is just below it.
+    assert_eq!(eval("(eq 100000000000000000000000 1e23)"), "false");
+    assert_eq!(eval("(le 100000000000000000000000 1e23)"), "false");
+    assert_eq!(eval("(le 1e23 100000000000000000000000)"), "true");
+    assert_eq!(eval("(eq (div 1 2) 0.5)"), "true");
+    // 0.1 is a little more than 1/10.
+    assert_eq!(eval("(eq (div 1 10) 0.1)"), "false");
+    assert_eq!(eval("(le 0.1 (div 1 10))"), "false");
+}
+
+#[test]
+fn compares_exact_numbers_with_infinities_and_nan() {
+    // 2^1100, which overflows a float.
+    let huge = "(mul 1267650600228229401496703205376 \
+                     (mul 1267650600228229401496703205376 \
+                          (mul 1267650600228229401496703205376 \
+                               1180591620717411303424)))";
+    assert_eq!(eval(&format!("(eq {} inf)", huge)), "false");
+    assert_eq!(eval(&format!("(le {} inf)", huge)), "true");
+    assert_eq!(eval(&format!("(le inf {})", huge)), "false");
+    assert_eq!(eval(&format!("(le -inf (sub 0 {}))", huge)), "true");
+    assert_eq!(eval("(eq 1 nan)"), "false");
+    assert_eq!(eval("(le 1 nan)"), "false");
+    assert_eq!(eval("(le nan 1)"), "false");
+}
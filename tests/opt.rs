@@ -0,0 +1,73 @@
+extern crate cam;
+
+use std::fs;
+use std::io::Read;
+use std::str::FromStr;
+
+use cam::cam::VM;
+use cam::compile::compile;
+use cam::lang::*;
+use cam::opt;
+use cam::parse::ParseFrom;
+use cam::sexp::Sexp;
+
+fn parse(src: &str) -> Exp {
+    Exp::parse_from(&Sexp::from_str(src).unwrap()).unwrap()
+}
+
+fn run(e: &Exp) -> String {
+    VM::run(compile(e)).unwrap().to_string()
+}
+
+// Every program in tests/programs must give the same result whether or not
+// it's optimized.
+#[test]
+fn optimizing_preserves_results() {
+    let mut count = 0;
+    for entry in fs::read_dir("tests/programs").unwrap() {
+        let path = entry.unwrap().path();
+        let mut src = String::new();
+        fs::File::open(&path).unwrap().read_to_string(&mut src).unwrap();
+        let e = parse(&src);
+        let optimized = opt::optimize(e.clone());
+        assert_eq!(run(&e), run(&optimized), "in {}", path.display());
+        count += 1;
+    }
+    assert!(count > 0);
+}
+
+#[test]
+fn beta_reduces_and_folds() {
+    let e = opt::optimize(parse("((fn (x) (add x 1)) 2)"));
+    assert_eq!(e.to_string(), "let x = 2 in add(x, 1)");
+}
+
+#[test]
+fn inlines_let_bound_functions() {
+    let e = opt::optimize(parse(
+        "(let ((y 3)) (let ((f (fn (x) (add x y)))) (let ((z 1)) (f z))))"));
+    assert_eq!(e.to_string(),
+               "let y = 3 in let z = 1 in let x = z in add(x, y)");
+}
+
+#[test]
+fn keeps_assigned_functions() {
+    let e = opt::optimize(parse(
+        "(let ((f (fn (x) x))) (let ((u (set! f car))) (f 1)))"));
+    assert_eq!(e.to_string(),
+               "let f = \\x -> x in let u = f := car in f(1)");
+}
+
+#[test]
+fn respects_the_budget() {
+    // Inlining f at every call would add far more than the whole program.
+    let e = opt::optimize(parse(
+        "(let ((f (fn (x) (add x (add x (add x (add x (add x (add x (add x
+                                                                  (add x x)))))))))))
+           (cons (f 1) (cons (f 2) (cons (f 3) (cons (f 4) (cons (f 5) (cons (f 6)
+             (cons (f 7) (cons (f 8) (cons (f 9) (cons (f 10) (cons (f 11)
+               (cons (f 12) nil)))))))))))))"));
+    let s = e.to_string();
+    assert!(s.starts_with("let f = "));
+    assert!(s.contains("f(12)"));
+}
@@ -0,0 +1,120 @@
+extern crate cam;
+
+use std::fs;
+use std::io::Read;
+use std::str::FromStr;
+
+use cam::cam::{VM,Code,Proto};
+use cam::cam::Instr::*;
+use cam::compile::compile;
+use cam::lang::*;
+use cam::parse::ParseFrom;
+use cam::peephole;
+use cam::sexp::Sexp;
+use cam::verify::{verify,verify_code,VerifyError};
+
+fn parse(src: &str) -> Exp {
+    Exp::parse_from(&Sexp::from_str(src).unwrap()).unwrap()
+}
+
+fn optimize(code: Code) -> Code {
+    let code = peephole::optimize_code(code);
+    assert_eq!(verify_code(&code), Ok(()));
+    code
+}
+
+fn run(code: Code) -> String {
+    VM::run(Proto::assemble(0, &code)).unwrap().to_string()
+}
+
+#[test]
+fn threads_jumps_into_returns() {
+    let code = optimize(vec![
+        Push(Lit::Bool(true)), If(2, 4),
+        Push(Lit::Int(1)), Jump(6),
+        Push(Lit::Int(2)), Jump(6),
+        Jump(7),
+        Return]);
+    assert_eq!(format!("{:?}", code),
+               "[Push(Bool(true)), If(2, 4), Push(Int(1)), Return, \
+                Push(Int(2)), Return]");
+    assert_eq!(run(code), "1");
+}
+
+#[test]
+fn if_with_identical_branches_pops() {
+    let code = optimize(vec![
+        Push(Lit::Int(1)), Push(Lit::Bool(false)), If(3, 3), Return]);
+    assert_eq!(format!("{:?}", code),
+               "[Push(Int(1)), Push(Bool(false)), Pop, Return]");
+    assert_eq!(run(code), "1");
+}
+
+#[test]
+fn merges_ifs_whose_branches_do_the_same() {
+    let code = optimize(compile(&parse(
+        "((fn (c) (let ((y (if c (add c 1) (add c 1)))) y)) 2)")).instrs());
+    let f = match code[0] {
+        Closure(ref f) => f.instrs(),
+        ref instr => panic!("expected a closure, got {:?}", instr),
+    };
+    assert_eq!(format!("{:?}", f),
+               "[Get(0), Pop, Get(0), Push(Int(1)), Prim(Add), Bind(1), \
+                Get(0), Return]");
+    assert_eq!(run(code), "3");
+    // Branches that differ anywhere are kept.
+    let code = compile(&parse("(fn (c) (if c (add c 1) (add c 2)))")).instrs();
+    let f = match code[0] { Closure(ref f) => f.instrs(), _ => unreachable!() };
+    assert!(peephole::optimize_code(f).iter().any(|i| match *i {
+        If(..) => true,
+        _ => false,
+    }));
+}
+
+#[test]
+fn drops_unreachable_code() {
+    let code = optimize(vec![
+        Jump(3), Push(Lit::Int(1)), Return, Push(Lit::Int(2)), Return]);
+    assert_eq!(format!("{:?}", code), "[Push(Int(2)), Return]");
+}
+
+#[test]
+fn nested_ifs() {
+    let e = parse("((fn (a b) (add (if a (if b 1 2) 3) 1)) true false)");
+    let code = compile(&e).instrs();
+    assert_eq!(verify_code(&code), Ok(()));
+    assert_eq!(run(optimize(code)), "3");
+}
+
+#[test]
+fn verifier_rejects_bad_code() {
+    assert_eq!(verify_code(&vec![Pop, Return]),
+               Err(VerifyError::Underflow { at: 0, depth: 0 }));
+    assert_eq!(verify_code(&vec![Push(Lit::Nil), Push(Lit::Nil), Return]),
+               Err(VerifyError::BadReturn { at: 2, depth: 2 }));
+    assert_eq!(verify_code(&vec![Push(Lit::Bool(true)), If(2, 3),
+                            Push(Lit::Nil), Push(Lit::Nil), Return]),
+               Err(VerifyError::Mismatch { at: 3, depth: 1, other: 0 }));
+    assert_eq!(verify_code(&vec![Jump(5)]),
+               Err(VerifyError::BadTarget { at: 0, target: 5 }));
+    assert_eq!(verify_code(&vec![Push(Lit::Nil)]), Err(VerifyError::FallsOffEnd));
+}
+
+// The programs from tests/programs compile to verifiable code, and still do
+// after the peephole pass, with the same results.
+#[test]
+fn programs_keep_their_stack_discipline() {
+    for entry in fs::read_dir("tests/programs").unwrap() {
+        let path = entry.unwrap().path();
+        let mut src = String::new();
+        fs::File::open(&path).unwrap().read_to_string(&mut src).unwrap();
+        let e = parse(&src);
+        let code = compile(&e);
+        assert_eq!(verify(&code), Ok(()), "in {}", path.display());
+        let before = VM::run(code).unwrap().to_string();
+        let optimized = peephole::optimize(&compile(&e));
+        assert_eq!(verify(&optimized), Ok(()), "in {}", path.display());
+        let after = VM::run(optimized).unwrap().to_string();
+        assert_eq!(before, after, "in {}", path.display());
+    }
+}
@@ -0,0 +1,101 @@
+extern crate cam;
+
+use std::str::FromStr;
+
+use cam::cam::{VM,RuntimeError,Instr,Proto};
+use cam::compile::compile;
+use cam::lang::*;
+use cam::parse::ParseFrom;
+use cam::prim::PrimError;
+use cam::sexp::Sexp;
+
+fn build(src: &str) -> Proto {
+    compile(&Exp::parse_from(&Sexp::from_str(src).unwrap()).unwrap())
+}
+
+fn run(src: &str) -> Result<String, RuntimeError> {
+    VM::run(build(src)).map(|v| v.to_string())
+}
+
+// The prim and apply instructions `src' compiles to, in order.
+fn calls(src: &str) -> Vec<String> {
+    build(src).instrs().iter().filter_map(|i| match *i {
+        Instr::Prim(p) => Some(format!("prim {}", p)),
+        Instr::Apply(n) => Some(format!("apply {}", n)),
+        Instr::TailApply(n) => Some(format!("tail-apply {}", n)),
+        _ => None,
+    }).collect()
+}
+
+fn eval(src: &str) -> String { run(src).unwrap() }
+
+#[test]
+fn dividing_by_zero() {
+    // Only exact division by exact zero is an error.
+    for src in &["(div 1 0)", "(div (div 1 2) 0)",
+                 "(div 100000000000000000000 0)"] {
+        match run(src) {
+            Err(RuntimeError::Prim(PrimError::DivideByZero)) => {}
+            r => panic!("{}: got {:?}", src, r),
+        }
+    }
+    assert_eq!(eval("(div 1.0 0)"), "inf");
+    assert_eq!(eval("(div -1.0 0)"), "-inf");
+    assert_eq!(eval("(div 1 0.0)"), "inf");
+    assert_eq!(eval("(div 0.0 0)"), "nan");
+}
+
+#[test]
+fn string_to_number() {
+    assert_eq!(eval("(string->number \"12\")"), "12");
+    assert_eq!(eval("(string->number \"-1.5\")"), "-1.5");
+    assert_eq!(eval("(string->number \"100000000000000000000\")"),
+               "100000000000000000000");
+    // Anything else in the string, even spaces, and it's not a number.
+    assert_eq!(eval("(string->number \"  12 \")"), "false");
+    assert_eq!(eval("(string->number \"12 \")"), "false");
+    assert_eq!(eval("(string->number \"12;\")"), "false");
+    assert_eq!(eval("(string->number \"\")"), "false");
+    assert_eq!(eval("(string->number \"abc\")"), "false");
+}
+
+#[test]
+fn type_errors() {
+    assert_eq!(run("(string-length 1)").unwrap_err().to_string(),
+               "string-length: expected string, got 1");
+    assert_eq!(run("(string-ref \"abc\" 5)").unwrap_err().to_string(),
+               "string-ref: argument out of range: 5");
+}
+
+#[test]
+fn saturated_calls_compile_to_prim() {
+    assert_eq!(calls("(add 1 2)"), ["prim add"]);
+    assert_eq!(calls("(car (cons 1 (add 2 3)))"),
+               ["prim add", "prim cons", "prim car"]);
+    // In tail position too, returning the result.
+    assert_eq!(format!("{:?}", build("(add 1 2)").instrs()),
+               "[Push(Int(1)), Push(Int(2)), Prim(Add), Return]");
+    assert_eq!(eval("(car (cons 1 (add 2 3)))"), "1");
+}
+
+#[test]
+fn other_uses_of_prims_apply_them() {
+    // Prims as values are applied like any other function.
+    assert_eq!(calls("(let ((f add)) (f 1 2))"), ["tail-apply 2"]);
+    assert_eq!(eval("(let ((f add)) (f 1 2))"), "3");
+    assert_eq!(calls("((fn (f) (f 1 2)) add)"), ["tail-apply 1"]);
+    assert_eq!(eval("((fn (f) (f 1 2)) add)"), "3");
+    // So are prims applied to the wrong number of arguments, which fail
+    // when run.
+    assert_eq!(calls("(add 1)"), ["tail-apply 1"]);
+    assert_eq!(calls("(car 1 2)"), ["tail-apply 2"]);
+    assert_eq!(calls("(cons (add 1) 2)"), ["apply 1", "prim cons"]);
+    match run("(add 1)") {
+        Err(RuntimeError::WrongArity { expected: 2, got: 1 }) => {}
+        r => panic!("got {:?}", r),
+    }
+    match run("(cons (car 1 2) 3)") {
+        Err(RuntimeError::WrongArity { expected: 1, got: 2 }) => {}
+        r => panic!("got {:?}", r),
+    }
+}
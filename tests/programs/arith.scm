@@ -0,0 +1 @@
+(add (mul 3 (sub 10 4)) (div 7 2))
@@ -0,0 +1,4 @@
+(let ((f (fn (x) (add x 1))))
+  (let ((a (f 1)))
+    (let ((u (set! f (fn (x) (mul x 10)))))
+      (cons a (f 2)))))
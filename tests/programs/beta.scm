@@ -0,0 +1 @@
+((fn (x y) (add x (mul y 2))) 3 4)
@@ -0,0 +1,5 @@
+(let ((n 0))
+  (let ((bump (fn (k) (set! n (add n k)))))
+    (let ((a (bump 1)) (b (bump 2)))
+      (let ((c (bump 3)))
+        n))))
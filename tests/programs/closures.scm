@@ -0,0 +1,3 @@
+(let ((make-adder (fn (n) (fn (x) (add x n)))))
+  (let ((add5 (make-adder 5)) (add7 (make-adder 7)))
+    (cons (add5 1) (cons (add7 1) nil))))
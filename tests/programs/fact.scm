@@ -0,0 +1,2 @@
+(let ((fact (fn (self n) (if (le n 1) 1 (mul n (self self (sub n 1)))))))
+  (fact fact 25))
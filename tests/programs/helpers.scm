@@ -0,0 +1,3 @@
+(let ((square (fn (x) (mul x x)))
+      (inc (fn (x) (add x 1))))
+  (add (square (inc 2)) (square 5)))
@@ -0,0 +1,3 @@
+(let ((twice (fn (g x) (g (g x))))
+      (dbl (fn (x) (mul x 2))))
+  (cons (twice dbl 5) (twice (fn (s) (string-append s "!")) "hi")))
@@ -0,0 +1,5 @@
+((fn (loop) (loop loop 0 1000))
+ (fn (self acc n)
+   (if (eq n 0) acc
+       (let ((m (sub n 1)))
+         (self self (add acc n) m)))))
@@ -0,0 +1,4 @@
+(let ((f (fn (x) (let ((y (add x 1))) (fn (z) (add y z))))))
+  (let ((g (f 10)))
+    (let ((y 100))
+      (add (g y) ((f y) 1)))))
@@ -0,0 +1,2 @@
+(let ((f (fn (x) (cons x '(b c)))))
+  (cons (f 'a) (symbol->string 'd)))
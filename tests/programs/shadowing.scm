@@ -0,0 +1,4 @@
+(let ((x 10))
+  (let ((f (fn (y) (add x y))))
+    (let ((x 1000))
+      ((fn (x) (f x)) 1))))
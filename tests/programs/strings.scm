@@ -0,0 +1,2 @@
+(let ((join (fn (xs) (string-join xs ", "))))
+  (join (string-split (string-upcase "a b c") " ")))
@@ -0,0 +1,100 @@
+extern crate cam;
+
+use std::str::FromStr;
+
+use cam::bigint::BigInt;
+use cam::cam::VM;
+use cam::compile::compile;
+use cam::lang::*;
+use cam::parse::ParseFrom;
+use cam::ratio::Ratio;
+use cam::sexp::Sexp;
+
+fn eval(src: &str) -> String {
+    let e = Exp::parse_from(&Sexp::from_str(src).unwrap()).unwrap();
+    VM::run(compile(&e)).unwrap().to_string()
+}
+
+fn ratio(num: &str, den: &str) -> Ratio {
+    Ratio::new(num.parse().unwrap(), den.parse().unwrap())
+}
+
+#[test]
+fn normalizes() {
+    assert_eq!(ratio("6", "4").to_string(), "3/2");
+    assert_eq!(ratio("6", "-4").to_string(), "-3/2");
+    assert_eq!(ratio("-6", "-4").to_string(), "3/2");
+    assert_eq!(ratio("0", "-5").to_string(), "0/1");
+    assert_eq!(ratio("100000000000000000000", "300000000000000000000")
+                   .to_string(), "1/3");
+    assert_eq!(ratio("6", "-4"), ratio("-3", "2"));
+    // Results in lowest terms with denominator 1 are integers.
+    assert_eq!(eval("(div 6 3)"), "2");
+    assert_eq!(eval("(add (div 1 3) (div 2 3))"), "1");
+    assert_eq!(eval("(mul (div 3 2) (div 2 3))"), "1");
+}
+
+#[test]
+fn handles_signs() {
+    assert_eq!(eval("(div -1 2)"), "-1/2");
+    assert_eq!(eval("(div 1 -2)"), "-1/2");
+    assert_eq!(eval("(div -1 -2)"), "1/2");
+    assert_eq!(eval("(sub (div 1 3) (div 1 2))"), "-1/6");
+    assert_eq!(eval("(cons (floor (div -7 2)) (ceiling (div -7 2)))"),
+               "(-4 . -3)");
+    assert_eq!(eval("(cons (truncate (div -7 2)) (round (div -7 2)))"),
+               "(-3 . -4)");
+    assert_eq!(eval("(cons (round (div 5 2)) (round (div -5 2)))"),
+               "(2 . -2)");
+    assert_eq!(eval("(le (div -1 2) (div -1 3))"), "true");
+    assert_eq!(eval("(numerator (div -6 4))"), "-3");
+    assert_eq!(eval("(denominator (div -6 4))"), "2");
+}
+
+#[test]
+fn mixes_with_bignums_and_floats() {
+    assert_eq!(eval("(add 100000000000000000000 (div 1 3))"),
+               "300000000000000000001/3");
+    assert_eq!(eval("(div 100000000000000000000 300000000000000000000)"),
+               "1/3");
+    assert_eq!(eval("(mul (div 1 100000000000000000000) \
+                          100000000000000000000)"), "1");
+    assert_eq!(eval("(add (div 1 2) 0.25)"), "0.75");
+    assert_eq!(eval("(mul (div 1 3) 3.0)"), "1.0");
+    assert_eq!(eval("(inexact->exact 0.375)"), "3/8");
+    assert_eq!(eval("(exact->inexact (div 1 8))"), "0.125");
+}
+
+#[test]
+fn converts_to_f64_with_one_rounding() {
+    assert_eq!(ratio("1", "3").to_f64(), 1.0 / 3.0);
+    assert_eq!(ratio("-2", "3").to_f64(), -2.0 / 3.0);
+    // Both parts overflow floats, but their quotient doesn't.
+    let big = format!("1{}", "0".repeat(400));
+    assert_eq!(ratio(&format!("{}1", big), &format!("3{}", "0".repeat(401)))
+                   .to_f64(), 1.0 / 3.0);
+    // 1 + 2^-53 + 2^-200 is just over halfway between 1 and the next float,
+    // and rounds up; truncating the quotient first would lose the 2^-200
+    // and round down to even.
+    let two = |n| BigInt::one().shl(n);
+    let r = Ratio::new(two(200).add(&two(147)).add(&BigInt::one()), two(200));
+    assert_eq!(r.to_f64(), 1.0 + 2f64.powi(-52));
+    assert_eq!(r.mul(&Ratio::from_int(BigInt::from_i64(-1))).to_f64(),
+               -1.0 - 2f64.powi(-52));
+    // Exactly halfway rounds to even.
+    let r = Ratio::new(two(53).add(&BigInt::one()), two(53));
+    assert_eq!(r.to_f64(), 1.0);
+}
+
+#[test]
+fn square_roots_of_exact_squares_are_exact() {
+    assert_eq!(eval("(sqrt (div 1 4))"), "1/2");
+    assert_eq!(eval("(sqrt (div 9 16))"), "3/4");
+    assert_eq!(eval("(sqrt 16)"), "4");
+    assert_eq!(eval("(sqrt 100000000000000000000000000000000000000000000)"),
+               "10000000000000000000000");
+    assert_eq!(eval("(sqrt 0)"), "0");
+    assert_eq!(eval("(sqrt (div 1 2))"), (0.5f64).sqrt().to_string());
+    assert_eq!(eval("(sqrt 2)"), (2f64).sqrt().to_string());
+    assert_eq!(eval("(sqrt -4)"), "nan");
+}
@@ -0,0 +1,63 @@
+extern crate cam;
+
+use std::str::FromStr;
+
+use cam::cam::{VM,Proto,RuntimeError};
+use cam::compile::compile;
+use cam::lang::*;
+use cam::parse::ParseFrom;
+use cam::prim::PrimError;
+use cam::sexp::Sexp;
+
+fn build(src: &str) -> Proto {
+    compile(&Exp::parse_from(&Sexp::from_str(src).unwrap()).unwrap())
+}
+
+fn error(src: &str) -> RuntimeError {
+    VM::run(build(src)).unwrap_err()
+}
+
+#[test]
+fn reports_errors() {
+    match error("(div 1 0)") {
+        RuntimeError::Prim(PrimError::DivideByZero) => {}
+        e => panic!("got {:?}", e),
+    }
+    match error("(add 1 'a)") {
+        RuntimeError::Prim(PrimError::Type { .. }) => {}
+        e => panic!("got {:?}", e),
+    }
+    assert_eq!(error("(1 2)").to_string(), "applying non-function: 1");
+    assert_eq!(error("((fn (x y) x) 1)").to_string(),
+               "function takes 2 arguments, got 1");
+    assert_eq!(error("((fn (f) (f 1 2)) car)").to_string(),
+               "function takes 1 argument, got 2");
+}
+
+#[test]
+fn leaves_the_vm_where_it_failed() {
+    let mut vm = VM::new(build("(let ((f (fn (x) (car x)))) (add 1 (f 2)))"))
+        .unwrap();
+    let err = loop {
+        match vm.step() {
+            Ok(()) => assert!(!vm.done()),
+            Err(e) => break e,
+        }
+    };
+    assert!(err.to_string().starts_with("car: expected"), "{}", err);
+    // We're inside f, with car's argument still on the stack, and trying
+    // again fails the same way.
+    assert_eq!(vm.depth(), 1);
+    assert_eq!(vm.stack().last().map(|v| v.to_string()), Some("2".to_string()));
+    let ip = vm.ip();
+    assert_eq!(vm.step().unwrap_err().to_string(), err.to_string());
+    assert_eq!(vm.ip(), ip);
+}
+
+#[test]
+fn functions_equal_only_themselves() {
+    assert_eq!(VM::run(build("(let ((f (fn (x) x)) (g (fn (x) x)))
+                                (cons (eq f f) (eq f g)))"))
+                   .unwrap().to_string(),
+               "(true . false)");
+}
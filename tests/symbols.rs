@@ -0,0 +1,97 @@
+extern crate cam;
+
+use std::collections::HashSet;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash,Hasher};
+use std::str::FromStr;
+
+use cam::cam::VM;
+use cam::compile::compile;
+use cam::lang::*;
+use cam::parse::ParseFrom;
+use cam::sexp::{Sexp,SexpKind};
+use cam::string::Symbol;
+
+fn eval(src: &str) -> String {
+    let e = Exp::parse_from(&Sexp::from_str(src).unwrap()).unwrap();
+    VM::run(compile(&e)).unwrap().to_string()
+}
+
+#[test]
+fn quoting() {
+    assert_eq!(eval("'a"), "a");
+    assert_eq!(eval("(quote a)"), "a");
+    assert_eq!(eval("'(a b c)"), "(a b c)");
+    assert_eq!(eval("'(a (1 \"s\") #\\c)"), "(a (1 \"s\") #\\c)");
+    assert_eq!(eval("'()"), "nil");
+    // Quoting a quote gives the list it stands for.
+    assert_eq!(eval("''a"), "(quote a)");
+    // Quoted symbols aren't variables, even when they name one.
+    assert_eq!(eval("(let ((x 1)) 'x)"), "x");
+    assert_eq!(eval("'add"), "add");
+}
+
+#[test]
+fn quote_takes_one_argument() {
+    let s = Sexp::from_str("(quote a b)").unwrap();
+    assert_eq!(Exp::parse_from(&s).unwrap_err().to_string(),
+               "quote takes 1 argument, got 2");
+}
+
+#[test]
+fn symbol_prims() {
+    assert_eq!(eval("(symbol? 'a)"), "true");
+    assert_eq!(eval("(symbol? \"a\")"), "false");
+    assert_eq!(eval("(symbol->string 'abc)"), "\"abc\"");
+    assert_eq!(eval("(string->symbol \"abc\")"), "abc");
+    assert_eq!(eval("(eq (string->symbol \"abc\") 'abc)"), "true");
+    assert_eq!(eval("(eq 'a \"a\")"), "false");
+}
+
+#[test]
+fn interned_symbols_are_identical() {
+    let a = Symbol::intern("interned");
+    let b = Symbol::intern(&(String::from("intern") + "ed"));
+    assert_eq!(a, b);
+    assert!(a.to_str() == *"interned");
+    assert!(Symbol::intern("interned") != Symbol::intern("Interned"));
+    // Reading the same name twice gives the same symbol.
+    let s = Sexp::from_str("(foo foo)").unwrap();
+    match s.kind {
+        SexpKind::List(ref v) => match (&v[0].kind, &v[1].kind) {
+            (&SexpKind::Symbol(ref x), &SexpKind::Symbol(ref y)) =>
+                assert_eq!(x, y),
+            k => panic!("expected symbols, got {:?}", k),
+        },
+        ref k => panic!("expected a list, got {:?}", k),
+    }
+    assert_eq!(eval("(eq 'foo (car '(foo)))"), "true");
+}
+
+#[test]
+fn symbols_hash_by_identity() {
+    let mut set = HashSet::new();
+    set.insert(Symbol::intern("a"));
+    set.insert(Symbol::intern("b"));
+    set.insert(Symbol::intern(&"a".to_string()));
+    assert_eq!(set.len(), 2);
+    assert!(set.contains(&Symbol::intern("a")));
+    // Symbols with the same name hash the same, so their hashes are
+    // consistent with equality.
+    let hash = |s: &Symbol| {
+        let mut h = DefaultHasher::new();
+        s.hash(&mut h);
+        h.finish()
+    };
+    assert_eq!(hash(&Symbol::intern("a")), hash(&Symbol::intern("a")));
+}
+
+#[test]
+fn symbols_order_by_name() {
+    let mut syms: Vec<Symbol> = ["c", "a", "b"].iter()
+        .map(|s| Symbol::intern(s)).collect();
+    syms.sort();
+    let names: Vec<&str> = syms.iter().map(|s| &**s).collect();
+    assert_eq!(names, ["a", "b", "c"]);
+    assert_eq!(eval("(le 'apple 'banana)"), "true");
+}
@@ -0,0 +1,141 @@
+extern crate cam;
+
+use std::str::FromStr;
+
+use cam::lang::*;
+use cam::parse::ParseFrom;
+use cam::sexp::{Sexp,Span};
+use cam::string::Symbol;
+
+fn error(src: &str) -> SyntaxError {
+    Exp::parse_from(&Sexp::from_str(src).unwrap()).unwrap_err()
+}
+
+fn span(start: usize, end: usize) -> Span {
+    Span { start: start, end: end }
+}
+
+#[test]
+fn reports_each_error_where_it_happened() {
+    let x = Symbol::intern("x");
+    assert_eq!(error("(add x 1)"), SyntaxError::UnboundVariable(x.clone(),
+                                                                span(5, 6)));
+    assert_eq!(error("(set! x 1)"), SyntaxError::UnboundVariable(x,
+                                                                 span(6, 7)));
+    assert_eq!(error("(if 1 2)"),
+               SyntaxError::BadArity { form: "if", expected: 3, got: 2,
+                                       span: span(0, 8) });
+    assert_eq!(error("(fn x x)"), SyntaxError::MalformedParams(span(4, 5)));
+    assert_eq!(error("(fn (x 1) x)"),
+               SyntaxError::MalformedParams(span(7, 8)));
+    assert_eq!(error("(let x 1)"),
+               SyntaxError::MalformedBindings(span(5, 6)));
+    assert_eq!(error("(let ((x)) 1)"),
+               SyntaxError::MalformedBindings(span(6, 9)));
+    assert_eq!(error("(let ((1 2)) 1)"),
+               SyntaxError::ExpectedSymbol(span(7, 8)));
+    assert_eq!(error("(set! 1 2)"), SyntaxError::ExpectedSymbol(span(6, 7)));
+    assert_eq!(error("(add ())"), SyntaxError::EmptyApplication(span(5, 7)));
+    assert_eq!(error("(app)"), SyntaxError::EmptyApplication(span(0, 5)));
+    let s = Sexp::from_str("frob").unwrap();
+    assert_eq!(Prim::parse_from(&s).unwrap_err(),
+               SyntaxError::UnknownPrim(Symbol::intern("frob"), span(0, 4)));
+    let s = Sexp::from_str("(1)").unwrap();
+    assert_eq!(Lit::parse_from(&s).unwrap_err(),
+               SyntaxError::NotALiteral(span(0, 3)));
+}
+
+#[test]
+fn messages() {
+    assert_eq!(error("(add x 1)").to_string(), "unbound variable `x`");
+    assert_eq!(error("(if 1 2)").to_string(), "if takes 3 arguments, got 2");
+    assert_eq!(error("(quote)").to_string(), "quote takes 1 argument, got 0");
+    assert_eq!(error("(fn x x)").to_string(),
+               "fn parameters must be a list of symbols");
+    assert_eq!(error("(let x 1)").to_string(),
+               "let bindings must be a list of (name expression) pairs");
+    assert_eq!(error("(add ())").to_string(),
+               "empty list is not an expression");
+    assert_eq!(error("(set! 1 2)").to_string(), "expected a symbol");
+}
+
+#[test]
+fn renders_the_offending_source() {
+    let src = "(add x 1)";
+    assert_eq!(error(src).render(src), "\
+error: unbound variable `x`
+ --> 1:6
+  |
+1 | (add x 1)
+  |      ^
+");
+    let src = "(if 1 2)";
+    assert_eq!(error(src).render(src), "\
+error: if takes 3 arguments, got 2
+ --> 1:1
+  |
+1 | (if 1 2)
+  | ^^^^^^^^
+");
+}
+
+#[test]
+fn renders_later_lines() {
+    // Only the line the span starts on is shown, and the carets stop at its
+    // end.
+    let src = "(let ((a 1))\n  (add a\n       b))";
+    assert_eq!(error(src).render(src), "\
+error: unbound variable `b`
+ --> 3:8
+  |
+3 |        b))
+  |        ^
+");
+    let src = "(let ((a 1))\n  (if a\n      1))";
+    assert_eq!(error(src).render(src), "\
+error: if takes 3 arguments, got 2
+ --> 2:3
+  |
+2 |   (if a
+  |   ^^^^^
+");
+    // The gutter widens to fit the line number.
+    let src = format!("{}(add x 1)", "\n".repeat(11));
+    assert_eq!(error(&src).render(&src), "\
+error: unbound variable `x`
+  --> 12:6
+   |
+12 | (add x 1)
+   |      ^
+");
+}
+
+#[test]
+fn counts_columns_in_characters() {
+    let src = "(add \"λλλ\" y)";
+    assert_eq!(error(src).render(src), "\
+error: unbound variable `y`
+ --> 1:12
+  |
+1 | (add \"λλλ\" y)
+  |            ^
+");
+    // Wide characters count as one column too.
+    let src = "(string-append \"日本\"\n  (string-append \"é\" z))";
+    assert_eq!(error(src).render(src), "\
+error: unbound variable `z`
+ --> 2:22
+  |
+2 |   (string-append \"é\" z))
+  |                      ^
+");
+    // Symbols are ASCII; others are rejected when read, rather than
+    // tripping over character boundaries.
+    assert!(Sexp::from_str("(λ 1)").is_err());
+}
+
+#[test]
+fn renders_spans_past_the_end() {
+    let msg = span(3, 10).render("ab", "oops");
+    assert_eq!(msg, "error: oops\n --> 1:3\n  |\n1 | ab\n  |   ^\n");
+}
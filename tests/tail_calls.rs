@@ -0,0 +1,61 @@
+extern crate cam;
+
+use std::str::FromStr;
+
+use cam::cam::{VM,Val};
+use cam::compile::compile;
+use cam::lang::*;
+use cam::parse::ParseFrom;
+use cam::sexp::Sexp;
+
+fn vm(src: &str) -> VM {
+    let s = Sexp::from_str(src).unwrap();
+    VM::new(compile(&Exp::parse_from(&s).unwrap())).unwrap()
+}
+
+// Runs `src' to completion, returning its value and the deepest the frame
+// stack got along the way.
+fn run(src: &str) -> (Val, usize) {
+    let mut vm = vm(src);
+    let mut depth = 0;
+    while !vm.done() {
+        vm.step().unwrap();
+        depth = depth.max(vm.depth());
+    }
+    (vm.value(), depth)
+}
+
+#[test]
+fn million_iteration_loop() {
+    let (val, depth) = run(
+        "((fn (loop) (loop loop 1000000))
+          (fn (self n) (if (eq n 0) 'done (self self (sub n 1)))))");
+    assert_eq!(val.to_string(), "done");
+    assert!(depth <= 1, "frame depth grew to {}", depth);
+}
+
+#[test]
+fn loop_through_let() {
+    let (val, depth) = run(
+        "((fn (loop) (loop loop 0 100000))
+          (fn (self acc n)
+            (if (eq n 0) acc
+                (let ((m (sub n 1))) (self self (add acc n) m)))))");
+    assert_eq!(val.to_string(), "5000050000");
+    assert!(depth <= 1, "frame depth grew to {}", depth);
+}
+
+#[test]
+fn non_tail_calls_still_return() {
+    let (val, _) = run(
+        "((fn (fact) (fact fact 20))
+          (fn (self n) (if (eq n 0) 1 (mul n (self self (sub n 1))))))");
+    assert_eq!(val.to_string(), "2432902008176640000");
+}
+
+#[test]
+fn tail_call_to_prim() {
+    let (val, depth) = run("((fn (x) (add x 1)) 41)");
+    assert_eq!(val.to_string(), "42");
+    assert_eq!(depth, 0);
+}
@@ -0,0 +1,107 @@
+extern crate cam;
+
+use std::cell::RefCell;
+use std::io::{self,Write};
+use std::rc::Rc;
+use std::str::FromStr;
+
+use cam::cam::{VM,Val,Proto};
+use cam::compile::compile;
+use cam::lang::*;
+use cam::parse::ParseFrom;
+use cam::sexp::Sexp;
+use cam::trace::{Trace,Tracer};
+
+fn build(src: &str) -> Proto {
+    compile(&Exp::parse_from(&Sexp::from_str(src).unwrap()).unwrap())
+}
+
+// Output we can still read once the VM has its tracer.
+#[derive(Clone)]
+struct Shared(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
+fn traced<F>(src: &str, trace: F) -> String
+    where F: FnOnce(Shared) -> Trace<Shared>
+{
+    let out = Shared(Rc::new(RefCell::new(vec![])));
+    let mut vm = VM::new(build(src)).unwrap();
+    vm.set_tracer(Some(Box::new(trace(out.clone()))));
+    vm.finish().unwrap();
+    let text = String::from_utf8(out.0.borrow().clone()).unwrap();
+    text
+}
+
+#[test]
+fn traces_calls() {
+    let src = "(let ((f (fn (x) (add x 1)))) (mul 2 (f 3)))";
+    assert_eq!(traced(src, Trace::calls), "call f [3]
+  add [3 1] => 4
+  return 4
+mul [2 4] => 8
+return 8
+");
+}
+
+#[test]
+fn traces_instructions() {
+    assert_eq!(traced("(car (cons 1 2))", Trace::all), "   0  (push 1)             []
+   2  (push 2)             [1]
+   4  (prim cons)          [1 2]
+cons [1 2] => (1 . 2)
+   6  (prim car)           [(1 . 2)]
+car [(1 . 2)] => 1
+   8  (return)             [1]
+return 1
+");
+}
+
+// Counts events, and checks they arrive in a sensible order.
+#[derive(Default)]
+struct Counts { instrs: usize, calls: usize, rets: usize, prims: usize }
+struct Counter(Rc<RefCell<Counts>>);
+
+impl Tracer for Counter {
+    fn instr(&mut self, _vm: &VM) { self.0.borrow_mut().instrs += 1 }
+    fn call(&mut self, vm: &VM, proto: &Proto, args: &[Val], tail: bool) {
+        assert_eq!(args.len(), proto.arity as usize);
+        // Every call here is a tail call, even the top level's.
+        assert!(tail);
+        assert_eq!(vm.depth(), 0);
+        self.0.borrow_mut().calls += 1
+    }
+    fn ret(&mut self, _vm: &VM, _val: &Val) { self.0.borrow_mut().rets += 1 }
+    fn prim(&mut self, _vm: &VM, _prim: Prim, _args: &[Val], _val: &Val) {
+        self.0.borrow_mut().prims += 1
+    }
+}
+
+#[test]
+fn custom_tracers() {
+    let counts = Rc::new(RefCell::new(Counts::default()));
+    let mut vm = VM::new(build("(let ((loop (fn (self n)
+                                    (if (eq n 0) n (self self (sub n 1))))))
+                                   (loop loop 3))")).unwrap();
+    vm.set_tracer(Some(Box::new(Counter(counts.clone()))));
+    vm.finish().unwrap();
+    let counts = counts.borrow();
+    // The top level's call, then three more; each eq but the last is
+    // followed by a sub; and the one frame left returns once.
+    assert_eq!((counts.calls, counts.rets, counts.prims), (4, 1, 7));
+    assert!(counts.instrs > counts.calls + counts.prims);
+}
+
+#[test]
+fn removing_the_tracer() {
+    let mut vm = VM::new(build("(add 1 2)")).unwrap();
+    vm.set_tracer(Some(Box::new(Trace::all(vec![]))));
+    assert!(vm.set_tracer(None).is_some());
+    vm.finish().unwrap();
+    assert_eq!(vm.value().to_string(), "3");
+}
@@ -0,0 +1,93 @@
+extern crate cam;
+
+use cam::bytecode::{IF,PRIM};
+use cam::cam::{VM,Proto,RuntimeError};
+use cam::parse::ParseFrom;
+use cam::sexp::Sexp;
+use cam::verify::{verify,VerifyError};
+
+fn assemble(src: &str) -> Proto {
+    Proto::parse_from(&Sexp::parse_all(src).unwrap()[..]).unwrap()
+}
+
+fn check(src: &str) -> Result<(), VerifyError> {
+    verify(&assemble(src))
+}
+
+#[test]
+fn checks_variables_against_the_env() {
+    assert_eq!(check("(proto (arity 0) (get 0) (return))"),
+               Err(VerifyError::BadVar { at: 0, index: 0, env: 0 }));
+    assert_eq!(check("(proto (arity 0) (push 1) (bind 1) (get 0) (unbind 1)
+                                       (set 0) (push nil) (return))"),
+               Err(VerifyError::BadVar { at: 4, index: 0, env: 0 }));
+    assert_eq!(check("(proto (arity 0) (unbind 1) (push nil) (return))"),
+               Err(VerifyError::BadVar { at: 0, index: 1, env: 0 }));
+}
+
+#[test]
+fn checks_env_sizes_agree_at_joins() {
+    assert_eq!(check("(proto (arity 0)
+                          (push 1) (push true) (if a b)
+                       a: (bind 1) (push 2) (jump c)
+                       b: (jump c)
+                       c: (return))"),
+               Err(VerifyError::EnvMismatch { at: 7, env: 1, other: 0 }));
+}
+
+#[test]
+fn closures_see_their_parents_env() {
+    // The inner proto sees `y' from the let and its own `x'.
+    assert_eq!(check("(proto p0 (arity 0)
+                          (push 1) (bind 1) (closure p1) (return))
+                      (proto p1 (arity 1) (get 1) (return))"), Ok(()));
+    assert_eq!(check("(proto p0 (arity 0) (closure p1) (return))
+                      (proto p1 (arity 1) (get 1) (return))"),
+               Err(VerifyError::BadVar { at: 0, index: 1, env: 1 }));
+    // A proto is checked against every env it's closed over with.
+    assert_eq!(check("(proto p0 (arity 0)
+                          (push 1) (bind 1) (closure p1) (unbind 1)
+                          (pop) (closure p1) (return))
+                      (proto p1 (arity 1) (get 1) (return))"),
+               Err(VerifyError::BadVar { at: 0, index: 1, env: 1 }));
+}
+
+#[test]
+fn every_path_returns() {
+    assert_eq!(check("(proto (arity 0) (push true) (if a b)
+                       a: (push 1) (return)
+                       b: (push 2))"),
+               Err(VerifyError::FallsOffEnd));
+}
+
+#[test]
+fn returns_leave_one_value() {
+    assert_eq!(check("(proto (arity 0) (push 1) (push 2) (return))"),
+               Err(VerifyError::BadReturn { at: 2, depth: 2 }));
+    assert_eq!(check("(proto (arity 0) (push add) (push 1) (push 2)
+                                       (tail-apply 2))"), Ok(()));
+    // What's below a tail call's function would be left on the caller's
+    // stack.
+    assert_eq!(check("(proto (arity 0) (push 5) (push add) (push 1) (push 2)
+                                       (tail-apply 2))"),
+               Err(VerifyError::BadReturn { at: 4, depth: 2 }));
+}
+
+#[test]
+fn vm_refuses_unverified_code() {
+    match VM::run(assemble("(proto (arity 0) (get 3) (return))")) {
+        Err(RuntimeError::Verify(VerifyError::BadVar { .. })) => {}
+        r => panic!("expected a verify error, got {:?}", r),
+    }
+}
+
+#[test]
+fn vm_refuses_undecodable_code() {
+    for code in vec![vec![99], vec![IF, 0], vec![PRIM, 255]] {
+        let proto = Proto { code: code, ..assemble("(proto (arity 0))") };
+        match VM::new(proto) {
+            Err(RuntimeError::BadCode(_)) => {}
+            r => panic!("expected bad code, got {:?}", r),
+        }
+    }
+}